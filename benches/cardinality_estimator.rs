@@ -3,7 +3,7 @@ static ALLOC: dhat::Alloc = dhat::Alloc;
 
 use std::hash::{BuildHasherDefault, Hash};
 
-use cardinality_estimator::CardinalityEstimator;
+use cardinality_estimator::{CardinalityEstimator, UnpackedSketch};
 use criterion::measurement::WallTime;
 use criterion::{
     black_box, criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion, Throughput,
@@ -62,6 +62,52 @@ fn benchmark(c: &mut Criterion) {
     }
     group.finish();
 
+    let mut group = c.benchmark_group("merge");
+    group.throughput(Throughput::Elements(1));
+    for &cardinality in &cardinalities {
+        bench_merge::<CardinalityEstimatorMut>(&mut group, cardinality);
+        bench_merge::<AmadeusStreamingEstimator>(&mut group, cardinality);
+        bench_merge::<ProbabilisticCollections>(&mut group, cardinality);
+        bench_merge::<HyperLogLog>(&mut group, cardinality);
+        bench_merge::<HyperLogLogPlus>(&mut group, cardinality);
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("merge_hll_layout");
+    group.throughput(Throughput::Elements(1));
+    for cardinality in [1 << 12, 1 << 16] {
+        bench_merge_hll_layout(&mut group, cardinality);
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("merge_exact_into_hll");
+    group.throughput(Throughput::Elements(1));
+    for cardinality in [2, 64] {
+        bench_merge_exact_into_hll(&mut group, cardinality);
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("merge_all");
+    for count in [16, 256] {
+        group.throughput(Throughput::Elements(count as u64));
+        bench_merge_all(&mut group, count);
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("insert_hll");
+    for cardinality in [1 << 12, 1 << 16] {
+        group.throughput(Throughput::Elements(cardinality as u64));
+        bench_insert_hll(&mut group, cardinality);
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("insert_hashes_sorted");
+    for cardinality in [1 << 16, 1 << 20] {
+        group.throughput(Throughput::Elements(cardinality as u64));
+        bench_insert_hashes_sorted(&mut group, cardinality);
+    }
+    group.finish();
+
     let results: Vec<StatRecord> = cardinalities
         .iter()
         .map(|&cardinality| StatRecord {
@@ -145,6 +191,151 @@ fn bench_estimate<E: CardinalityEstimatorTrait<usize>>(
     );
 }
 
+/// Benchmark merging an estimator of given cardinality into an empty one,
+/// which is the typical first step of reducing many estimators.
+fn bench_merge<E: CardinalityEstimatorTrait<usize>>(
+    group: &mut BenchmarkGroup<WallTime>,
+    cardinality: usize,
+) {
+    group.bench_with_input(
+        BenchmarkId::new(E::name(), cardinality),
+        &cardinality,
+        |b, &cardinality| {
+            let mut rhs = E::new();
+            for i in 0..black_box(cardinality) {
+                rhs.insert(black_box(&i));
+            }
+            b.iter(|| {
+                let mut lhs = E::new();
+                lhs.merge(black_box(&rhs));
+                lhs
+            });
+        },
+    );
+}
+
+/// Benchmark merging two `HyperLogLog` estimators of given cardinality stored
+/// in packed and unpacked register layouts.
+fn bench_merge_hll_layout(group: &mut BenchmarkGroup<WallTime>, cardinality: usize) {
+    let lhs: CardinalityEstimator<usize> = (0..cardinality).collect();
+    let rhs: CardinalityEstimator<usize> = (cardinality..2 * cardinality).collect();
+    group.bench_with_input(
+        BenchmarkId::new("packed", cardinality),
+        &cardinality,
+        |b, _| {
+            b.iter(|| {
+                let mut lhs = lhs.clone();
+                lhs.merge(black_box(&rhs));
+                lhs
+            });
+        },
+    );
+
+    let (lhs, rhs) = (lhs.to_unpacked(), rhs.to_unpacked());
+    group.bench_with_input(
+        BenchmarkId::new("unpacked", cardinality),
+        &cardinality,
+        |b, _| {
+            b.iter(|| {
+                let mut lhs: UnpackedSketch = lhs.clone();
+                lhs.merge_unpacked(black_box(&rhs));
+                lhs
+            });
+        },
+    );
+}
+
+/// Benchmark merging `HyperLogLog` estimator into exact estimator of given cardinality,
+/// which allocates a single copy of `rhs` registers.
+fn bench_merge_exact_into_hll(group: &mut BenchmarkGroup<WallTime>, cardinality: usize) {
+    let lhs: CardinalityEstimator<usize> = (0..cardinality).collect();
+    let rhs: CardinalityEstimator<usize> = (0..1 << 16).collect();
+    group.bench_with_input(
+        BenchmarkId::new("cardinality-estimator", cardinality),
+        &cardinality,
+        |b, _| {
+            b.iter(|| {
+                let mut lhs = lhs.clone();
+                lhs.merge(black_box(&rhs));
+                lhs
+            });
+        },
+    );
+}
+
+/// Benchmark reducing given count of exact estimators with heavily overlapping items
+/// with `merge_all` and `merge_all_prefiltered`.
+fn bench_merge_all(group: &mut BenchmarkGroup<WallTime>, count: usize) {
+    let estimators: Vec<CardinalityEstimator<usize>> =
+        (0..count).map(|i| (i % 4..i % 4 + 64).collect()).collect();
+    group.bench_with_input(BenchmarkId::new("merge_all", count), &count, |b, _| {
+        b.iter(|| CardinalityEstimator::merge_all(black_box(&estimators)));
+    });
+    group.bench_with_input(
+        BenchmarkId::new("merge_all_prefiltered", count),
+        &count,
+        |b, _| {
+            b.iter(|| CardinalityEstimator::merge_all_prefiltered(black_box(&estimators)));
+        },
+    );
+}
+
+/// Benchmark inserting hashes into `HyperLogLog` representation, dominated by register
+/// updates of number of zero registers and harmonic sum.
+fn bench_insert_hll(group: &mut BenchmarkGroup<WallTime>, cardinality: usize) {
+    let mut seed = 42;
+    let hashes: Vec<u64> = (0..cardinality).map(|_| wyhash::wyrng(&mut seed)).collect();
+    let hll = CardinalityEstimator::<usize>::new_dense();
+    group.bench_with_input(
+        BenchmarkId::new("cardinality-estimator", cardinality),
+        &cardinality,
+        |b, _| {
+            b.iter(|| {
+                let mut e = hll.clone();
+                for &hash in black_box(&hashes) {
+                    e.insert_hash(hash);
+                }
+                e
+            });
+        },
+    );
+}
+
+/// Benchmark inserting hashes into `HyperLogLog` representation in random order
+/// and sorted by register index.
+fn bench_insert_hashes_sorted(group: &mut BenchmarkGroup<WallTime>, cardinality: usize) {
+    let mut seed = 42;
+    let mut hashes: Vec<u64> = (0..cardinality).map(|_| wyhash::wyrng(&mut seed)).collect();
+    let mut hll = CardinalityEstimator::<usize>::new();
+    hll.reserve(CardinalityEstimator::<usize>::ARRAY_MAX + 1);
+    group.bench_with_input(
+        BenchmarkId::new("unsorted", cardinality),
+        &cardinality,
+        |b, _| {
+            b.iter(|| {
+                let mut e = hll.clone();
+                for &hash in black_box(&hashes) {
+                    e.insert_hash(hash);
+                }
+                e
+            });
+        },
+    );
+
+    hashes.sort_unstable_by_key(|&hash| hash & ((1 << 12) - 1));
+    group.bench_with_input(
+        BenchmarkId::new("sorted", cardinality),
+        &cardinality,
+        |b, _| {
+            b.iter(|| {
+                let mut e = hll.clone();
+                e.insert_hashes_sorted(black_box(&hashes));
+                e
+            });
+        },
+    );
+}
+
 fn measure_allocations<E: CardinalityEstimatorTrait<usize>>(cardinality: usize) -> String {
     let _profiler = dhat::Profiler::builder().testing().build();
     let mut estimator = E::new();
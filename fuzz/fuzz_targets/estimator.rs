@@ -17,7 +17,7 @@ fuzz_target!(|data: &[u8]| {
         estimator1.insert(&chunk);
         assert!(estimator1.estimate() > 0);
         assert!(estimator1.size_of() > 0);
-
+        assert!(estimator1.verify_invariants().is_ok());
     }
 
     let mut estimator2 = CardinalityEstimator::<&[u8]>::new();
@@ -25,7 +25,9 @@ fuzz_target!(|data: &[u8]| {
         estimator2.insert(&chunk);
         assert!(estimator2.estimate() > 0);
         assert!(estimator2.size_of() > 0);
+        assert!(estimator2.verify_invariants().is_ok());
     }
 
     estimator1.merge(&estimator2);
+    assert!(estimator1.verify_invariants().is_ok());
 });
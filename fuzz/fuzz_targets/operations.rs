@@ -0,0 +1,8 @@
+#![no_main]
+
+use cardinality_estimator::fuzz::fuzz_insert_sequence;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    fuzz_insert_sequence(data);
+});
@@ -10,15 +10,26 @@
 //! - data[0..N]    - store `N` encoded hashes
 //! - data[N..]     - store zeros used for future hashes
 
-use std::fmt::{Debug, Formatter};
-use std::mem::{size_of, size_of_val};
-use std::ops::Deref;
-use std::slice;
+use core::fmt::{Debug, Formatter};
+use core::mem::{size_of, size_of_val};
+use core::ops::Deref;
+use core::slice;
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
 
 use crate::hyperloglog::HyperLogLog;
-use crate::representation::RepresentationTrait;
+use crate::representation::{InvariantError, RepresentationTrait};
 
-/// Maximum number of elements stored in array representation
+/// Maximum number of elements stored in array representation.
+///
+/// There is no intermediate hash set representation: all exact cardinalities above
+/// `Small` representation capacity are kept in a single contiguous array searched by
+/// auto-vectorized linear scan, which stays cache friendly up to this capacity.
+/// Array length is stored in 8 bits of `data` field, so capacity can't exceed 128
+/// (the largest power of two fitting into 8 bits). Hence this threshold isn't configurable:
+/// it can't be raised, while lowering it would only give up exact estimates earlier.
 pub(crate) const MAX_CAPACITY: usize = 128;
 /// Bit offset of the array's length
 const LEN_OFFSET: usize = 56;
@@ -40,18 +51,7 @@ impl<'a, const P: usize, const W: usize> Array<'a, P, W> {
     /// Returns true on success, false otherwise.
     #[inline]
     pub(crate) fn insert(&mut self, h: u32) -> bool {
-        let found = if self.cap == 4 {
-            contains_fixed_vectorized::<4>(self.arr.try_into().unwrap(), h)
-        } else if self.cap == 8 {
-            contains_fixed_vectorized::<8>(self.arr.try_into().unwrap(), h)
-        } else {
-            // calculate rounded up slice length for efficient look up in batches
-            let rlen = 16 * self.len.div_ceil(16);
-            // SAFETY: `rlen` guaranteed to be within `self.arr` boundaries
-            contains_vectorized::<16>(unsafe { self.arr.get_unchecked(..rlen) }, h)
-        };
-
-        if found {
+        if self.contains(h) {
             return true;
         }
 
@@ -63,7 +63,8 @@ impl<'a, const P: usize, const W: usize> Array<'a, P, W> {
         }
 
         if self.cap < MAX_CAPACITY {
-            // double array capacity up to `MAX_CAPACITY`
+            // double array capacity up to `MAX_CAPACITY`; new array is fully initialized
+            // before the old one is freed, so no step in between can unwind
             let new_arr = Self::from_vec(vec![0; self.cap * 2], self.len + 1);
             new_arr.arr[..self.len].copy_from_slice(self.arr);
             new_arr.arr[self.len] = h;
@@ -75,12 +76,87 @@ impl<'a, const P: usize, const W: usize> Array<'a, P, W> {
         false
     }
 
-    /// Create new instance of `Array` representation from vector
+    /// Return true if encoded hash is stored in `Array` representation.
+    ///
+    /// Lookups scan whole batches including zeroed unused slots, which can't match valid
+    /// encoded hashes as their rank is never zero, while zero itself is never stored.
+    #[inline]
+    pub(crate) fn contains(&self, h: u32) -> bool {
+        if h == 0 {
+            return false;
+        }
+        if !VECTORIZED {
+            return self.deref().contains(&h);
+        }
+        if self.cap == 4 {
+            contains_fixed_vectorized::<4>(self.arr[..].try_into().unwrap(), h)
+        } else if self.cap == 8 {
+            contains_fixed_vectorized::<8>(self.arr[..].try_into().unwrap(), h)
+        } else {
+            // calculate rounded up slice length for efficient look up in batches
+            let rlen = 16 * self.len.div_ceil(16);
+            // SAFETY: `rlen` guaranteed to be within `self.arr` boundaries
+            contains_vectorized::<16>(unsafe { self.arr.get_unchecked(..rlen) }, h)
+        }
+    }
+
+    /// Merge `rhs` into `Array` representation allocating resulting array at most once.
+    /// Returns true on success, false if union exceeds `MAX_CAPACITY` leaving `self` unchanged.
+    ///
+    /// Missing hashes are counted first, then either appended in place if they fit into
+    /// current capacity, or copied together with stored hashes into a single new array.
+    #[inline]
+    pub(crate) fn merge(&mut self, rhs: &Array<P, W>) -> bool {
+        let missing = rhs.iter().filter(|&&h| !self.contains(h)).count();
+        let len = self.len + missing;
+        if len > MAX_CAPACITY {
+            return false;
+        }
+
+        if len > self.cap {
+            let mut new_arr = Self::from_vec(vec![0; len.next_power_of_two()], self.len);
+            new_arr.arr[..self.len].copy_from_slice(&self.arr[..self.len]);
+            for &h in rhs.iter() {
+                if !self.contains(h) {
+                    new_arr.arr[new_arr.len] = h;
+                    new_arr.len += 1;
+                }
+            }
+            unsafe { self.drop() };
+            *self = new_arr;
+        } else {
+            // `rhs` hashes are distinct, so appended hashes can't match subsequent ones
+            for &h in rhs.iter() {
+                if !self.contains(h) {
+                    self.arr[self.len] = h;
+                    self.len += 1;
+                }
+            }
+        }
+        debug_assert_eq!(self.len, len);
+        true
+    }
+
+    /// Convert `Array` representation into vector of stored encoded hashes
+    /// reusing its allocation.
+    /// SAFETY: caller must ensure that `self.arr` holds valid slice elements, which must not be
+    /// accessed or freed afterwards. Allocation capacity is exactly `self.cap` elements, as
+    /// arrays are only created by `from_vec` from vectors with capacity equal to their length.
+    #[inline]
+    pub(crate) unsafe fn into_vec(self) -> Vec<u32> {
+        let mut vec = Vec::from_raw_parts(self.arr.as_mut_ptr(), self.cap, self.cap);
+        vec.truncate(self.len);
+        vec
+    }
+
+    /// Create new instance of `Array` representation from vector, whose capacity must be
+    /// equal to its length, as array is freed as boxed slice of that length.
     #[inline]
     pub(crate) fn from_vec(mut arr: Vec<u32>, len: usize) -> Array<'a, P, W> {
+        debug_assert_eq!(arr.capacity(), arr.len());
         let cap = arr.len();
         let ptr = arr.as_mut_ptr();
-        std::mem::forget(arr);
+        core::mem::forget(arr);
         // SAFETY: valid pointer from vector being used to create slice reference
         let arr = unsafe { slice::from_raw_parts_mut(ptr, cap) };
         Self { len, cap, arr }
@@ -125,17 +201,46 @@ impl<'a, const P: usize, const W: usize> RepresentationTrait for Array<'a, P, W>
     fn to_data(&self) -> usize {
         (self.len << LEN_OFFSET) | (PTR_MASK & self.arr.as_ptr() as usize) | 1
     }
+
+    /// Verify array length, that stored hashes are distinct and non-zero,
+    /// and that unused capacity is zeroed
+    fn verify_invariants(&self) -> Result<(), InvariantError> {
+        if self.len <= 2 || self.len > MAX_CAPACITY {
+            return Err(InvariantError::ArrayInvalidLength(self.len));
+        }
+        if let Some(idx) = self.deref().iter().position(|&h| h == 0) {
+            return Err(InvariantError::ArrayZeroHash(idx));
+        }
+        let mut items = self.deref().to_vec();
+        items.sort_unstable();
+        if let Some(w) = items.windows(2).find(|w| w[0] == w[1]) {
+            return Err(InvariantError::ArrayDuplicateHash(w[0]));
+        }
+        if let Some(idx) = self.arr[self.len..].iter().position(|&h| h != 0) {
+            return Err(InvariantError::ArrayNonZeroPadding(self.len + idx));
+        }
+        Ok(())
+    }
 }
 
 impl<'a, const P: usize, const W: usize> Debug for Array<'a, P, W> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         f.write_str(&self.to_string())
     }
 }
 
 impl<'a, const P: usize, const W: usize> PartialEq for Array<'a, P, W> {
+    /// Compare stored encoded hashes, checking lengths first as a cheap early-out
     fn eq(&self, other: &Self) -> bool {
-        self.deref() == other.deref()
+        self.len == other.len && self.deref() == other.deref()
+    }
+}
+
+impl<const P: usize, const W: usize> Clone for Array<'_, P, W> {
+    /// Clone `Array` representation
+    #[inline]
+    fn clone(&self) -> Self {
+        Self::from_vec(self.arr.to_vec(), self.len)
     }
 }
 
@@ -145,7 +250,17 @@ impl<'a, const P: usize, const W: usize> From<usize> for Array<'a, P, W> {
     fn from(data: usize) -> Self {
         let ptr = (data & PTR_MASK) as *mut u32;
         let len = data >> LEN_OFFSET;
+        if len == 0 {
+            // Valid `Array` representation always stores at least 3 items, so zero length can
+            // only come from corrupted `data`, whose pointer must not be dereferenced.
+            return Self {
+                len: 0,
+                cap: 0,
+                arr: &mut [],
+            };
+        }
         let cap = len.next_power_of_two();
+        // SAFETY: non-empty `Array` representation points to slice of `cap` elements.
         let arr = unsafe { slice::from_raw_parts_mut(ptr, cap) };
         Self { len, cap, arr }
     }
@@ -159,6 +274,13 @@ impl<'a, const P: usize, const W: usize> Deref for Array<'a, P, W> {
     }
 }
 
+/// Whether lookups use batched searches, which compilers auto-vectorize into SIMD instructions.
+///
+/// `force_scalar` feature switches to plain early-exit scalar search instead, which exists only
+/// to compare or profile both paths. It's not needed for reproducibility: there is no runtime
+/// dispatch of CPU specific instructions, and both paths return identical results anyway.
+pub(crate) const VECTORIZED: bool = !cfg!(feature = "force_scalar");
+
 /// Vectorized linear array search benefiting from SIMD instructions (e.g. AVX2).
 ///
 /// Input slice length assumed to be divisible by `N` to perform efficient
@@ -182,3 +304,64 @@ fn contains_fixed_vectorized<const N: usize>(a: [u32; N], v: u32) -> bool {
     }
     res
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test]
+    fn test_zero_length_data() {
+        // array representation tag with null pointer and zero length
+        let arr = Array::<12, 6>::from(1);
+        assert_eq!(arr.estimate(), 0);
+        assert!(arr.is_empty());
+        assert!(!arr.contains(42));
+        assert_eq!(
+            arr.verify_invariants(),
+            Err(InvariantError::ArrayInvalidLength(0))
+        );
+    }
+
+    #[test]
+    fn test_eq() {
+        let lhs = Array::<12, 6>::from_vec(vec![1, 2, 3, 0], 3);
+        let same = Array::<12, 6>::from_vec(vec![1, 2, 3, 0, 0, 0, 0, 0], 3);
+        let different = Array::<12, 6>::from_vec(vec![1, 2, 4, 0], 3);
+        let longer = Array::<12, 6>::from_vec(vec![1, 2, 3, 4], 4);
+        assert!(lhs == same);
+        assert!(lhs != different);
+        assert!(lhs != longer);
+        for mut arr in [lhs, same, different, longer] {
+            unsafe { arr.drop() };
+        }
+    }
+
+    #[test_case(vec![1, 2, 3, 0], 3; "capacity 4")]
+    #[test_case(vec![1, 2, 3, 4, 5, 0, 0, 0], 5; "capacity 8")]
+    #[test_case((1..=17).chain([0; 15]).collect(), 17; "capacity 32")]
+    fn test_contains_zero_slots(items: Vec<u32>, len: usize) {
+        let mut arr = Array::<12, 6>::from_vec(items, len);
+        assert!(!arr.contains(0));
+        for h in 1..=len as u32 {
+            assert!(arr.contains(h));
+        }
+        assert!(!arr.contains(len as u32 + 1));
+        unsafe { arr.drop() };
+    }
+
+    #[test]
+    fn test_contains_matches_scalar_search() {
+        for len in [3, 4, 5, 8, 9, 16, 17, 100, 128] {
+            let cap = usize::next_power_of_two(len);
+            let items: Vec<u32> = (1..=len as u32).map(|i| i << 6 | 1).collect();
+            let mut padded = vec![0; cap];
+            padded[..len].copy_from_slice(&items);
+            let mut arr = Array::<12, 6>::from_vec(padded, len);
+            for h in (0..=cap as u32 + 1).flat_map(|i| [i << 6, i << 6 | 1]) {
+                assert_eq!(arr.contains(h), items.contains(&h), "{len} {h}");
+            }
+            unsafe { arr.drop() };
+        }
+    }
+}
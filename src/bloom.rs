@@ -0,0 +1,68 @@
+//! ## Bloom filter of encoded hashes
+//! Used by `CardinalityEstimator::merge_all_prefiltered` to skip re-inserting encoded hashes
+//! which were already merged. Bloom filter may report false positives, so positive answers
+//! must be verified against the estimator itself, while negative answers are always exact.
+
+/// Number of bits in the filter, sized for `Array` representation's `MAX_CAPACITY` hashes
+/// with less than 1% false positive rate.
+const NUM_BITS: usize = 4096;
+/// Number of `u64` words holding filter bits
+const NUM_WORDS: usize = NUM_BITS / 64;
+
+/// Bloom filter of encoded hashes using two bit probes per hash
+pub(crate) struct BloomFilter {
+    words: [u64; NUM_WORDS],
+}
+
+impl BloomFilter {
+    /// Create new empty Bloom filter
+    #[inline]
+    pub(crate) fn new() -> Self {
+        Self {
+            words: [0; NUM_WORDS],
+        }
+    }
+
+    /// Add encoded hash to the filter
+    #[inline]
+    pub(crate) fn insert(&mut self, h: u32) {
+        for bit in Self::probes(h) {
+            self.words[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Return false if encoded hash was definitely never added to the filter
+    #[inline]
+    pub(crate) fn may_contain(&self, h: u32) -> bool {
+        Self::probes(h)
+            .iter()
+            .all(|&bit| self.words[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Return bit positions of encoded hash, derived from its multiplicative hash as
+    /// encoded hashes of consecutive register indices differ only in their high bits.
+    #[inline]
+    fn probes(h: u32) -> [usize; 2] {
+        let x = (h as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        [(x >> 32) as usize % NUM_BITS, (x >> 48) as usize % NUM_BITS]
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bloom_filter() {
+        let mut filter = BloomFilter::new();
+        for h in (1..=128).map(|i| i << 6 | 1) {
+            filter.insert(h);
+            assert!(filter.may_contain(h));
+        }
+        let false_positives = (129..10_129)
+            .map(|i| i << 6 | 1)
+            .filter(|&h| filter.may_contain(h))
+            .count();
+        assert!(false_positives < 100, "{false_positives}");
+    }
+}
@@ -0,0 +1,358 @@
+//! # Byte encoding module for CardinalityEstimator
+//!
+//! This module provides compact binary encoding of `CardinalityEstimator`, allowing to store
+//! estimators or pass them through queues and channels without any serialization framework.
+//!
+//! Encoded estimator consists of 4 bytes header followed by representation payload:
+//! - byte 0        - format version `FORMAT_VERSION`, currently `1`
+//! - byte 1        - precision parameter `P`
+//! - byte 2        - width parameter `W`
+//! - byte 3        - representation tag: `0` - small, `1` - array, `3` - hyperloglog
+//! - bytes 4..     - representation payload:
+//!   - small: 8 bytes of little-endian `data` word;
+//!   - array: little-endian `u32` encoded hashes;
+//!   - hyperloglog: little-endian `u32` hyperloglog slice.
+use core::hash::{BuildHasher, Hash};
+
+use alloc::vec::Vec;
+
+use crate::array::MAX_CAPACITY;
+use crate::estimator::CardinalityEstimator;
+use crate::hyperloglog::HyperLogLog;
+use crate::representation::{Representation, RepresentationKind, RepresentationTrait};
+use crate::small::Small;
+
+/// Current version of byte encoding format.
+///
+/// Version is bumped on every incompatible change of the format, so that readers reject
+/// estimators encoded by newer versions of the crate instead of misinterpreting them.
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Size of encoded estimator header
+const HEADER_LEN: usize = 4;
+
+/// Decoding error
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    /// Message is not valid protobuf wire format or encoded estimator is truncated
+    InvalidMessage,
+    /// Encoding format version `found` is not supported, i.e. it is newer than
+    /// `max_supported` version or is unknown
+    UnsupportedVersion { found: u32, max_supported: u32 },
+    /// Encoded `P` or `W` parameters don't match estimator's parameters
+    ParamsMismatch,
+    /// Representation tag or payload is invalid
+    InvalidRepresentation,
+}
+
+impl<T, S, const P: usize, const W: usize> CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// Encode `CardinalityEstimator` into bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (tag, payload) = self.to_payload();
+        let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+        bytes.extend_from_slice(&[FORMAT_VERSION, P as u8, W as u8, tag]);
+        bytes.extend_from_slice(&payload);
+        bytes
+    }
+
+    /// Decode `CardinalityEstimator` from bytes produced by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError>
+    where
+        S: Default,
+    {
+        let (tag, payload) = Self::split_header(bytes)?;
+        Self::from_payload(tag, payload)
+    }
+
+    /// Return cardinality estimate of estimator encoded by `to_bytes` without decoding it,
+    /// e.g. of memory mapped estimators.
+    ///
+    /// Estimate is computed in place without allocations: exact representations are counted
+    /// by their payload and `HyperLogLog` representation by its stored accounting data.
+    /// Only header and payload lengths are validated, so `from_bytes` must be used to fully
+    /// validate the encoded estimator.
+    pub fn estimate_from_bytes(bytes: &[u8]) -> Result<usize, DecodeError> {
+        let (tag, payload) = Self::split_header(bytes)?;
+        match RepresentationKind::from_u8(tag) {
+            Some(RepresentationKind::Small) => {
+                let bytes = payload.try_into();
+                let data =
+                    u64::from_le_bytes(bytes.map_err(|_| DecodeError::InvalidRepresentation)?);
+                if data & 3 != tag as u64 {
+                    return Err(DecodeError::InvalidRepresentation);
+                }
+                Ok(Small::<P, W>::from(data as usize).estimate())
+            }
+            Some(RepresentationKind::Array)
+                if payload.len().is_multiple_of(4)
+                    && (3..=MAX_CAPACITY).contains(&(payload.len() / 4)) =>
+            {
+                Ok(payload.len() / 4)
+            }
+            Some(RepresentationKind::Hll)
+                if payload.len() == HyperLogLog::<P, W>::HLL_SLICE_LEN * 4 =>
+            {
+                let word =
+                    |i: usize| u32::from_le_bytes(payload[i * 4..(i + 1) * 4].try_into().unwrap());
+                let sum = f32::from_bits(word(1)) as f64;
+                Ok((HyperLogLog::<P, W>::estimate_from(word(0), sum) + 0.5) as usize)
+            }
+            _ => Err(DecodeError::InvalidRepresentation),
+        }
+    }
+
+    /// Validate header of encoded estimator returning its representation tag and payload
+    fn split_header(bytes: &[u8]) -> Result<(u8, &[u8]), DecodeError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(DecodeError::InvalidMessage);
+        }
+        let (header, payload) = bytes.split_at(HEADER_LEN);
+        if header[0] != FORMAT_VERSION {
+            return Err(DecodeError::UnsupportedVersion {
+                found: header[0] as u32,
+                max_supported: FORMAT_VERSION as u32,
+            });
+        }
+        if header[1] as usize != P || header[2] as usize != W {
+            return Err(DecodeError::ParamsMismatch);
+        }
+        Ok((header[3], payload))
+    }
+
+    /// Decode estimator from bytes produced by `to_bytes` and merge it into `self`
+    pub fn merge_from_bytes(&mut self, bytes: &[u8]) -> Result<(), DecodeError>
+    where
+        S: Default,
+    {
+        self.merge(&Self::from_bytes(bytes)?);
+        Ok(())
+    }
+
+    /// Decode and merge each of encoded estimators into `self` returning number of merged
+    /// estimators. Merging stops at the first decoding error, keeping estimators merged so far.
+    pub fn merge_bytes_iter<I>(&mut self, iter: I) -> Result<usize, DecodeError>
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+        S: Default,
+    {
+        let mut merged = 0;
+        for bytes in iter {
+            self.merge_from_bytes(&bytes)?;
+            merged += 1;
+        }
+        Ok(merged)
+    }
+
+    /// Return representation tag and little-endian encoded representation payload.
+    ///
+    /// Payload is canonical, i.e. estimators storing the same hashes or registers produce
+    /// identical payloads regardless of insertion order: exact representations store sorted
+    /// hashes and `HyperLogLog` representation stores harmonic sum recomputed from registers.
+    pub(crate) fn to_payload(&self) -> (u8, Vec<u8>) {
+        match self.representation() {
+            Representation::Small(small) => {
+                let [h1, h2] = small.items();
+                let (h1, h2) = if h2 != 0 && h2 < h1 {
+                    (h2, h1)
+                } else {
+                    (h1, h2)
+                };
+                let data = ((h1 as u64) << 2) | ((h2 as u64) << 33);
+                (RepresentationKind::Small as u8, data.to_le_bytes().to_vec())
+            }
+            Representation::Array(arr) => {
+                let mut items = arr.to_vec();
+                items.sort_unstable();
+                (RepresentationKind::Array as u8, u32s_to_le_bytes(&items))
+            }
+            Representation::Hll(hll) => {
+                let mut bytes = u32s_to_le_bytes(hll.data);
+                for (i, word) in hll.canonical_accounting().into_iter().enumerate() {
+                    bytes[i * 4..(i + 1) * 4].copy_from_slice(&word.to_le_bytes());
+                }
+                (RepresentationKind::Hll as u8, bytes)
+            }
+        }
+    }
+
+    /// Create `CardinalityEstimator` from representation tag and payload
+    pub(crate) fn from_payload(tag: u8, payload: &[u8]) -> Result<Self, DecodeError>
+    where
+        S: Default,
+    {
+        let (data, opt_vec) = match RepresentationKind::from_u8(tag) {
+            Some(RepresentationKind::Small) => {
+                let bytes = payload.try_into();
+                let data =
+                    u64::from_le_bytes(bytes.map_err(|_| DecodeError::InvalidRepresentation)?);
+                (data as usize, None)
+            }
+            Some(RepresentationKind::Array | RepresentationKind::Hll)
+                if payload.len().is_multiple_of(4) =>
+            {
+                let vec = payload
+                    .chunks_exact(4)
+                    .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+                    .collect();
+                (tag as usize, Some(vec))
+            }
+            _ => return Err(DecodeError::InvalidRepresentation),
+        };
+        if data & 3 != tag as usize {
+            return Err(DecodeError::InvalidRepresentation);
+        }
+        Representation::try_from(data, opt_vec).map_err(|_| DecodeError::InvalidRepresentation)
+    }
+}
+
+/// Convert `u32` slice into little-endian bytes
+fn u32s_to_le_bytes(items: &[u32]) -> Vec<u8> {
+    items.iter().flat_map(|h| h.to_le_bytes()).collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0; "empty set")]
+    #[test_case(1; "single element")]
+    #[test_case(2; "two distinct elements")]
+    #[test_case(3; "three distinct elements")]
+    #[test_case(100; "hundred distinct elements")]
+    #[test_case(10000; "ten thousand distinct elements")]
+    fn test_bytes_round_trip(n: usize) {
+        let mut original = CardinalityEstimator::<usize>::new();
+        for i in 0..n {
+            original.insert(&i);
+        }
+
+        let bytes = original.to_bytes();
+        let mut decoded = CardinalityEstimator::<usize>::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.to_bytes(), bytes);
+        assert_eq!(decoded.estimate(), original.estimate());
+
+        // decoded estimator must remain fully functional
+        for i in n..n + 200 {
+            original.insert(&i);
+            decoded.insert(&i);
+        }
+        assert_eq!(decoded.estimate(), original.estimate());
+    }
+
+    #[test]
+    fn test_bytes_invalid() {
+        let bytes = CardinalityEstimator::<usize>::new().to_bytes();
+        let decode = |bytes: &[u8]| CardinalityEstimator::<usize>::from_bytes(bytes).unwrap_err();
+
+        assert_eq!(decode(&bytes[..3]), DecodeError::InvalidMessage);
+        assert_eq!(
+            decode(&[&[FORMAT_VERSION + 1], &bytes[1..]].concat()),
+            DecodeError::UnsupportedVersion {
+                found: 2,
+                max_supported: 1
+            }
+        );
+        assert_eq!(
+            decode(&[&bytes[..1], &[10], &bytes[2..]].concat()),
+            DecodeError::ParamsMismatch
+        );
+        assert_eq!(
+            decode(&[&bytes[..3], &[2], &bytes[4..]].concat()),
+            DecodeError::InvalidRepresentation
+        );
+        assert_eq!(decode(&bytes[..8]), DecodeError::InvalidRepresentation);
+        assert_eq!(
+            decode(&[&bytes[..3], &[RepresentationKind::Hll as u8], &[0; 12]].concat()),
+            DecodeError::InvalidRepresentation
+        );
+    }
+
+    #[test_case(0; "empty set")]
+    #[test_case(1; "single element")]
+    #[test_case(2; "two distinct elements")]
+    #[test_case(3; "three distinct elements")]
+    #[test_case(128; "full array")]
+    #[test_case(10000; "ten thousand distinct elements")]
+    fn test_estimate_from_bytes(n: usize) {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..n {
+            e.insert(&i);
+        }
+        let bytes = e.to_bytes();
+        assert_eq!(
+            CardinalityEstimator::<usize>::estimate_from_bytes(&bytes),
+            Ok(CardinalityEstimator::<usize>::from_bytes(&bytes)
+                .unwrap()
+                .estimate())
+        );
+
+        // invalid encodings are rejected like by `from_bytes`
+        let estimate = CardinalityEstimator::<usize>::estimate_from_bytes;
+        for invalid in [
+            &bytes[..3],
+            &bytes[..bytes.len() - 1],
+            &[&bytes[..3], &[2]].concat(),
+        ] {
+            assert_eq!(
+                estimate(invalid),
+                Err(CardinalityEstimator::<usize>::from_bytes(invalid).unwrap_err())
+            );
+        }
+    }
+
+    #[test]
+    fn test_merge_bytes_iter() {
+        let encoded: Vec<Vec<u8>> = (0..10)
+            .map(|i| {
+                let mut e = CardinalityEstimator::<usize>::new();
+                for j in 0..i * 50 {
+                    e.insert(&(i * 1000 + j));
+                }
+                e.to_bytes()
+            })
+            .collect();
+
+        let mut expected = CardinalityEstimator::<usize>::new();
+        for bytes in &encoded {
+            expected.merge_from_bytes(bytes).unwrap();
+        }
+
+        let mut merged = CardinalityEstimator::<usize>::new();
+        assert_eq!(merged.merge_bytes_iter(encoded.clone()), Ok(10));
+        assert_eq!(merged, expected);
+
+        // merging stops at the first decoding error
+        let mut merged = CardinalityEstimator::<usize>::new();
+        let mut invalid = encoded.clone();
+        invalid.insert(3, vec![1, 12, 6]);
+        assert_eq!(
+            merged.merge_bytes_iter(invalid),
+            Err(DecodeError::InvalidMessage)
+        );
+        let mut expected = CardinalityEstimator::<usize>::new();
+        expected.merge_bytes_iter(encoded[..3].to_vec()).unwrap();
+        assert_eq!(merged, expected);
+    }
+
+    #[test_case(2; "small representation")]
+    #[test_case(100; "array representation")]
+    #[test_case(10_000; "hyperloglog representation")]
+    fn test_bytes_canonical(n: usize) {
+        let forward: CardinalityEstimator<usize> = (0..n).collect();
+        let mut backward = CardinalityEstimator::<usize>::new();
+        for i in (0..n).rev() {
+            backward.insert(&i);
+        }
+        assert_eq!(forward.to_bytes(), backward.to_bytes());
+
+        let mut merged = CardinalityEstimator::<usize>::new();
+        merged.merge(&(n / 2..n).collect());
+        merged.merge(&(0..n / 2).collect());
+        assert_eq!(merged.to_bytes(), forward.to_bytes());
+    }
+}
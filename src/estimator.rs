@@ -1,79 +1,650 @@
-use std::fmt::{Debug, Formatter};
-use std::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
-use std::marker::PhantomData;
-use std::ops::Deref;
+use core::fmt::{Debug, Formatter, Write};
+use core::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+use core::marker::PhantomData;
+use core::mem::{size_of, ManuallyDrop};
+use core::ops::Deref;
+#[cfg(feature = "cache_estimate")]
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
 
 use wyhash::WyHash;
 
-use crate::representation::{Representation, RepresentationTrait};
+use crate::array::{Array, MAX_CAPACITY};
+use crate::bloom::BloomFilter;
+use crate::hyperloglog::HyperLogLog;
+use crate::math;
+use crate::representation::{
+    InvariantError, Representation, RepresentationKind, RepresentationTrait,
+};
+use crate::small::SMALL_CAPACITY;
 
 /// Ensure that only 64-bit architecture is being used.
 #[cfg(target_pointer_width = "64")]
-pub struct CardinalityEstimator<T, H = WyHash, const P: usize = 12, const W: usize = 6>
-where
+pub struct CardinalityEstimator<
+    T,
+    S = BuildHasherDefault<WyHash>,
+    const P: usize = 12,
+    const W: usize = 6,
+> where
     T: Hash + ?Sized,
-    H: Hasher + Default,
+    S: BuildHasher,
 {
     /// Data field represents tagged pointer with its format described in lib.rs
     pub(crate) data: usize,
-    /// Zero-sized build hasher
-    build_hasher: BuildHasherDefault<H>,
+    /// Build hasher, zero-sized for `BuildHasherDefault` (default)
+    build_hasher: S,
+    /// Insertion statistics
+    #[cfg(feature = "stats")]
+    stats: InsertStats,
+    /// Harmonic sum recompute interval and state
+    #[cfg(feature = "recompute")]
+    recompute: RecomputeInterval,
+    /// Cached estimate of `HyperLogLog` representation or `NO_CACHED_ESTIMATE`
+    #[cfg(feature = "cache_estimate")]
+    cached_estimate: AtomicUsize,
     /// Zero-sized phantom data for type `T`
     _phantom_data: PhantomData<T>,
 }
 
-impl<T, H, const P: usize, const W: usize> CardinalityEstimator<T, H, P, W>
+impl<T, H, const P: usize, const W: usize> CardinalityEstimator<T, BuildHasherDefault<H>, P, W>
 where
     T: Hash + ?Sized,
     H: Hasher + Default,
+{
+    /// Creates new instance of `CardinalityEstimator` in const context, e.g. to initialize
+    /// `static` empty estimator, as `Default` of build hasher can't be called in const context.
+    #[inline]
+    pub const fn new_const() -> Self {
+        Self::with_hasher(BuildHasherDefault::new())
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
 {
     /// Ensure that `P` and `W` are in correct range at compile time
-    const VALID_PARAMS: () = assert!(P >= 4 && P <= 18 && W >= 4 && W <= 6);
+    const VALID_PARAMS: () = assert!(Self::check_params().is_ok());
+
+    /// Maximum cardinality stored in `Small` representation,
+    /// inserting more distinct items switches to `Array` representation
+    pub const SMALL_MAX: usize = SMALL_CAPACITY;
+
+    /// Maximum cardinality stored in `Array` representation,
+    /// inserting more distinct items switches to `HyperLogLog` representation
+    pub const ARRAY_MAX: usize = MAX_CAPACITY;
 
     /// Creates new instance of `CardinalityEstimator`
     #[inline]
-    pub fn new() -> Self {
+    pub fn new() -> Self
+    where
+        S: Default,
+    {
+        Self::with_hasher(S::default())
+    }
+
+    /// Creates new instance of `CardinalityEstimator` validating `P` and `W` at runtime.
+    ///
+    /// Unlike `new`, which fails to compile for out of range parameters, this constructor
+    /// returns `ParamError`, so it can be used by generic code instantiated with arbitrary
+    /// parameters.
+    #[inline]
+    pub fn checked_new() -> Result<Self, ParamError>
+    where
+        S: Default,
+    {
+        Self::check_params()?;
+        Ok(Self::with_hasher_unchecked(S::default()))
+    }
+
+    /// Creates new instance of `CardinalityEstimator` starting in `HyperLogLog` representation,
+    /// e.g. for workloads which always reach high cardinalities, skipping upgrades through
+    /// exact representations at the cost of allocating all registers upfront.
+    #[inline]
+    pub fn new_dense() -> Self
+    where
+        S: Default,
+    {
+        let mut estimator = Self::new();
+        estimator.reset_to_hyperloglog();
+        estimator
+    }
+
+    /// Creates new instance of `CardinalityEstimator` from precomputed 64-bit hashes,
+    /// e.g. produced by an upstream system, see `insert_hash`.
+    #[inline]
+    pub fn from_hashes<I>(hashes: I) -> Self
+    where
+        I: IntoIterator<Item = u64>,
+        S: Default,
+    {
+        let mut estimator = Self::new();
+        for hash in hashes {
+            estimator.insert_hash(hash);
+        }
+        estimator
+    }
+
+    /// Creates new instance of `CardinalityEstimator` using given `build_hasher`.
+    ///
+    /// Only estimators using equivalent build hashers (e.g. clones of the same randomly
+    /// seeded build hasher) can be merged, otherwise the same items are hashed differently.
+    #[inline]
+    pub const fn with_hasher(build_hasher: S) -> Self {
         // compile time check of params
         #[allow(clippy::let_unit_value)]
         let _ = Self::VALID_PARAMS;
 
+        Self::with_hasher_unchecked(build_hasher)
+    }
+
+    /// Return error if `P` or `W` is out of supported range
+    const fn check_params() -> Result<(), ParamError> {
+        if P < 4 || P > 18 {
+            return Err(ParamError::InvalidPrecision(P));
+        }
+        if W < 4 || W > 6 {
+            return Err(ParamError::InvalidWidth(W));
+        }
+        Ok(())
+    }
+
+    /// Creates new instance of `CardinalityEstimator` without checking params
+    #[inline]
+    const fn with_hasher_unchecked(build_hasher: S) -> Self {
         Self {
             // Start with empty small representation
             data: 0,
-            build_hasher: BuildHasherDefault::default(),
+            build_hasher,
+            #[cfg(feature = "stats")]
+            stats: InsertStats {
+                inserts: 0,
+                duplicates: 0,
+                upgrades: 0,
+            },
+            #[cfg(feature = "recompute")]
+            recompute: RecomputeInterval {
+                interval: 0,
+                inserts: 0,
+            },
+            #[cfg(feature = "cache_estimate")]
+            cached_estimate: AtomicUsize::new(NO_CACHED_ESTIMATE),
             _phantom_data: PhantomData,
         }
     }
 
+    /// Return reference to estimator's build hasher
+    #[inline]
+    pub fn hasher(&self) -> &S {
+        &self.build_hasher
+    }
+
+    /// Return precision parameter `P`, i.e. `HyperLogLog` representation uses `2^P` registers
+    #[inline]
+    pub const fn p(&self) -> usize {
+        P
+    }
+
+    /// Return register width parameter `W` in bits
+    #[inline]
+    pub const fn w(&self) -> usize {
+        W
+    }
+
     /// Insert a hashable item into `CardinalityEstimator`
     #[inline]
     pub fn insert(&mut self, item: &T) {
-        let hash = self.build_hasher.hash_one(&item);
+        let hash = self.build_hasher.hash_one(item);
+        self.insert_hash(hash);
+    }
+
+    /// Insert integer key into `CardinalityEstimator`, hashing it with a single `write_u64`.
+    ///
+    /// This is the fast path for integer keys: it skips generic `Hash` dispatch of `T` and
+    /// produces the same hash as `insert(&x)` of `CardinalityEstimator<u64>`, so both can be
+    /// mixed. Narrower integers (e.g. `u32` or `i64`) should be converted into `u64` first.
+    #[inline]
+    pub fn insert_u64(&mut self, x: u64) {
+        let mut hasher = self.build_hasher.build_hasher();
+        hasher.write_u64(x);
+        self.insert_hash(hasher.finish());
+    }
+
+    /// Insert floating point value into `CardinalityEstimator`, hashing its canonical bit pattern.
+    ///
+    /// Floats don't implement `Hash`, while hashing their raw bits counts `0.0` and `-0.0`
+    /// (as well as NaNs with different payloads) as distinct values. This method maps `-0.0`
+    /// to `0.0` and all NaNs to `f64::NAN` before hashing. Values are hashed as `u64` bits,
+    /// so it shouldn't be mixed with inserting other items, e.g. use `CardinalityEstimator<u64>`.
+    #[inline]
+    pub fn insert_f64(&mut self, x: f64) {
+        let bits = if x.is_nan() {
+            f64::NAN.to_bits()
+        } else if x == 0.0 {
+            0
+        } else {
+            x.to_bits()
+        };
+        let hash = self.build_hasher.hash_one(bits);
         self.insert_hash(hash);
     }
 
+    /// Insert `f32` floating point value into `CardinalityEstimator` canonicalized the same way as
+    /// `insert_f64`. Value is losslessly widened to `f64`, so equal `f32` and `f64` values
+    /// are counted once.
+    #[inline]
+    pub fn insert_f32(&mut self, x: f32) {
+        self.insert_f64(x as f64);
+    }
+
+    /// Insert batch of items into `CardinalityEstimator` returning number of items which
+    /// weren't seen before (within hash collisions chance).
+    ///
+    /// Novelty can only be detected by exact representations, so `None` is returned if
+    /// estimator is (or becomes while inserting the batch) `HyperLogLog` representation.
+    /// All items are inserted in either case.
+    pub fn insert_batch_count_new(&mut self, items: &[T]) -> Option<usize>
+    where
+        T: Sized,
+    {
+        let before = self.estimate_exact();
+        for item in items {
+            self.insert(item);
+        }
+        Some(self.estimate_exact()? - before?)
+    }
+
+    /// Insert a hashable item into `CardinalityEstimator` and return updated cardinality estimate.
+    ///
+    /// This is as cheap as `insert` followed by `estimate`: exact representations track their
+    /// length, while `HyperLogLog` representation maintains number of zero registers and
+    /// registers' harmonic sum during insertion, so no registers are rescanned.
+    #[inline]
+    pub fn insert_and_estimate(&mut self, item: &T) -> usize {
+        self.insert(item);
+        self.estimate()
+    }
+
     /// Return cardinality estimate
     #[inline]
     pub fn estimate(&self) -> usize {
-        self.representation().estimate()
+        #[cfg(feature = "cache_estimate")]
+        if let Some(estimate) = self.cached_estimate() {
+            return estimate;
+        }
+        let estimate = self.representation().estimate();
+        // only `HyperLogLog` estimate is worth caching, exact estimates are just lengths
+        #[cfg(feature = "cache_estimate")]
+        if self.representation_kind() == RepresentationKind::Hll {
+            self.cached_estimate.store(estimate, Ordering::Relaxed);
+        }
+        estimate
+    }
+
+    /// Return cardinality estimate as `u64` regardless of target pointer width.
+    ///
+    /// `HyperLogLog` representation can estimate cardinalities beyond `u32::MAX`, which
+    /// `estimate` would truncate on 32-bit targets.
+    #[inline]
+    pub fn estimate_u64(&self) -> u64 {
+        match self.representation() {
+            Representation::Hll(hll) => (hll.estimate_f64() + 0.5) as u64,
+            representation => representation.estimate() as u64,
+        }
+    }
+
+    /// Return cardinality estimate multiplied by `factor`, e.g. to extrapolate estimate of
+    /// a stream sampled with rate `1 / factor` to the full population (experimental).
+    ///
+    /// Result is rounded to the nearest integer and clamped into `[0..usize::MAX]` range,
+    /// so negative and `NaN` factors yield zero.
+    #[inline]
+    pub fn scaled_estimate(&self, factor: f64) -> usize {
+        let scaled = self.estimate() as f64 * factor;
+        if scaled.is_nan() || scaled <= 0.0 {
+            return 0;
+        }
+        // float to integer casts saturate at `usize::MAX`
+        (scaled + 0.5) as usize
+    }
+
+    /// Return cached estimate if it's still valid
+    #[cfg(feature = "cache_estimate")]
+    #[inline]
+    fn cached_estimate(&self) -> Option<usize> {
+        let estimate = self.cached_estimate.load(Ordering::Relaxed);
+        (estimate != NO_CACHED_ESTIMATE).then_some(estimate)
+    }
+
+    /// Invalidate cached estimate, must be called whenever `HyperLogLog` representation changes
+    #[inline]
+    fn invalidate_estimate(&mut self) {
+        #[cfg(feature = "cache_estimate")]
+        {
+            *self.cached_estimate.get_mut() = NO_CACHED_ESTIMATE;
+        }
+    }
+
+    /// Return fraction of non-zero registers, i.e. `(M - zeros) / M`, for `HyperLogLog`
+    /// representation or `None` for exact representations.
+    ///
+    /// Density close to `1.0` means that most registers are filled
+    /// and larger precision `P` may be worth using.
+    #[inline]
+    pub fn register_density(&self) -> Option<f64> {
+        match self.representation() {
+            Representation::Hll(hll) => {
+                let m = HyperLogLog::<P, W>::M as f64;
+                Some((m - hll.data[0] as f64) / m)
+            }
+            _ => None,
+        }
+    }
+
+    /// Return Shannon entropy (in bits) of distribution of register values for `HyperLogLog`
+    /// representation or `None` for exact representations.
+    ///
+    /// This is a diagnostic signal of registers' state rather than a cardinality measure:
+    /// it's zero while all registers hold the same value and grows as ranks spread out.
+    pub fn register_entropy(&self) -> Option<f64> {
+        let Representation::Hll(hll) = self.representation() else {
+            return None;
+        };
+        let m = HyperLogLog::<P, W>::M as f64;
+        let entropy = hll
+            .histogram()
+            .iter()
+            .filter(|&&count| count != 0)
+            .map(|&count| {
+                let p = count as f64 / m;
+                -p * math::ln(p)
+            })
+            .sum::<f64>();
+        Some(entropy / core::f64::consts::LN_2)
+    }
+
+    /// Return histogram of register ranks as CSV with `rank,count` header followed by one row
+    /// per non-empty rank, for `HyperLogLog` representation or `None` for exact representations.
+    ///
+    /// Histograms exported from many sketches can be used for offline fitting of
+    /// bias correction coefficients.
+    pub fn export_histogram_csv(&self) -> Option<String> {
+        let Representation::Hll(hll) = self.representation() else {
+            return None;
+        };
+        let mut csv = String::from("rank,count\n");
+        for (rank, &count) in hll.histogram().iter().enumerate() {
+            if count != 0 {
+                // writing into `String` never fails
+                let _ = writeln!(csv, "{rank},{count}");
+            }
+        }
+        Some(csv)
+    }
+
+    /// Return median rank of registers for `HyperLogLog` representation or `None` for exact
+    /// representations, computed from registers' histogram.
+    ///
+    /// Median rank shows how "deep" the sketch is: once most registers are filled it grows
+    /// roughly as `log2(estimate / 2^P) + 1`.
+    pub fn median_register_rank(&self) -> Option<u32> {
+        let Representation::Hll(hll) = self.representation() else {
+            return None;
+        };
+        let half = HyperLogLog::<P, W>::M.div_ceil(2) as u32;
+        let mut count = 0;
+        hll.histogram()
+            .iter()
+            .position(|&c| {
+                count += c;
+                count >= half
+            })
+            .map(|rank| rank as u32)
+    }
+
+    /// Return warning if `HyperLogLog` registers look produced by a non-uniform hasher
+    /// (e.g. identity hasher of integers), or `None` otherwise and for exact representations.
+    ///
+    /// This is a best-effort diagnostic: uniformly distributed hashes produce geometrically
+    /// distributed ranks, so at most about half of non-zero registers share the same rank,
+    /// while poor hashers tend to put almost all registers into a single rank.
+    pub fn hash_quality_warning(&self) -> Option<&'static str> {
+        /// Minimal number of non-zero registers for the check to be meaningful
+        const MIN_NON_ZERO: u32 = 64;
+
+        let Representation::Hll(hll) = self.representation() else {
+            return None;
+        };
+        let histogram = hll.histogram();
+        let non_zero = HyperLogLog::<P, W>::M as u32 - histogram[0];
+        let max_count = histogram[1..].iter().copied().max().unwrap_or(0);
+        // more than 90% of non-zero registers share the same rank
+        (non_zero >= MIN_NON_ZERO && max_count as u64 * 10 > non_zero as u64 * 9).then_some(
+            "register ranks aren't geometrically distributed, hasher output is likely not uniform",
+        )
+    }
+
+    /// Return rank of register `idx` for `HyperLogLog` representation, or `None` for exact
+    /// representations and out of `[0..2^P)` range indices.
+    #[inline]
+    pub fn register(&self, idx: u32) -> Option<u32> {
+        match self.representation() {
+            Representation::Hll(hll) if (idx as usize) < HyperLogLog::<P, W>::M => {
+                Some(hll.get_register(idx))
+            }
+            _ => None,
+        }
+    }
+
+    /// Return copy of `HyperLogLog` registers and accounting data for `HyperLogLog`
+    /// representation or `None` for exact representations, e.g. to evaluate
+    /// alternative estimation formulas.
+    pub fn hll_snapshot(&self) -> Option<HllSnapshot> {
+        match self.representation() {
+            Representation::Hll(hll) => Some(HllSnapshot {
+                p: P,
+                w: W,
+                zeros: hll.data[0],
+                harmonic_sum: f32::from_bits(hll.data[1]) as f64,
+                registers: (0..HyperLogLog::<P, W>::M as u32)
+                    .map(|idx| hll.get_register(idx))
+                    .collect(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Return exact cardinality (within hash collisions chance) for `Small` and `Array`
+    /// representations, or `None` once estimator switched to approximate `HyperLogLog`
+    /// representation.
+    #[inline]
+    pub fn estimate_exact(&self) -> Option<usize> {
+        match self.representation() {
+            Representation::Hll(_) => None,
+            representation => Some(representation.estimate()),
+        }
+    }
+
+    /// Return relative standard error of current estimate: `0.0` for exact representations
+    /// (within hash collisions chance) and `1.04 / sqrt(2^P)` for `HyperLogLog` representation.
+    #[inline]
+    pub fn estimate_relative_std_error(&self) -> f64 {
+        match self.representation() {
+            Representation::Hll(_) => 1.04 / math::sqrt(HyperLogLog::<P, W>::M as f64),
+            _ => 0.0,
+        }
+    }
+
+    /// Return cardinality estimate together with its exactness and 95% confidence interval.
+    ///
+    /// Exact representations (`Small` and `Array`) report bounds equal to the estimate, while
+    /// `HyperLogLog` representation reports `estimate * (1 ± 1.96 * 1.04 / sqrt(2^P))` bounds.
+    #[inline]
+    pub fn estimate_report(&self) -> EstimateReport {
+        let value = self.estimate();
+        if !matches!(self.representation(), Representation::Hll(_)) {
+            return EstimateReport {
+                value,
+                is_exact: true,
+                lower: value,
+                upper: value,
+            };
+        }
+
+        let margin = value as f64 * 1.96 * 1.04 / math::sqrt((1 << P) as f64);
+        EstimateReport {
+            value,
+            is_exact: false,
+            lower: math::floor((value as f64 - margin).max(0.0)) as usize,
+            upper: math::ceil(value as f64 + margin) as usize,
+        }
+    }
+
+    /// Return diagnostic metrics of `CardinalityEstimator` bundled for export as gauges,
+    /// e.g. into OpenMetrics or Prometheus exporters.
+    #[inline]
+    pub fn metrics(&self) -> SketchMetrics {
+        let heap_bytes = match self.representation() {
+            Representation::Small(_) => 0,
+            representation => representation.size_of() - size_of::<usize>(),
+        };
+        SketchMetrics {
+            estimate: self.estimate(),
+            representation: self.representation_kind().as_str(),
+            heap_bytes,
+            relative_std_error: self.estimate_relative_std_error(),
+        }
+    }
+
+    /// Return kind of current representation
+    #[inline]
+    pub fn representation_kind(&self) -> RepresentationKind {
+        self.representation().kind()
+    }
+
+    /// Return cardinality estimate rounded using given rounding `mode`.
+    ///
+    /// Exact representations return exact count for all modes, while `HyperLogLog`
+    /// representation rounds its fractional estimate, e.g. `RoundMode::Floor` or
+    /// `RoundMode::Ceil` can be used to stay conservative. `estimate` uses `RoundMode::Nearest`.
+    #[inline]
+    pub fn estimate_rounded(&self, mode: RoundMode) -> usize {
+        match self.representation() {
+            Representation::Hll(hll) => {
+                let estimate = hll.estimate_f64();
+                match mode {
+                    RoundMode::Floor => math::floor(estimate) as usize,
+                    RoundMode::Ceil => math::ceil(estimate) as usize,
+                    RoundMode::Nearest => (estimate + 0.5) as usize,
+                }
+            }
+            representation => representation.estimate(),
+        }
+    }
+
+    /// Return `true` if `CardinalityEstimator` has no items inserted
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.data == 0
+    }
+
+    /// Take estimator out leaving empty estimator in its place, similar to `Option::take`.
+    /// Heap allocated representation is moved into returned estimator without copying.
+    #[inline]
+    pub fn take(&mut self) -> Self
+    where
+        S: Default,
+    {
+        core::mem::take(self)
     }
 
-    /// Merge cardinality estimators
+    /// Merge cardinality estimators.
+    ///
+    /// Union of exact representations is always stored in the smallest exact representation
+    /// holding it losslessly: `Small` for up to `SMALL_MAX` items, otherwise `Array` with
+    /// capacity of the next power of two of union size, so no compaction is needed afterwards.
     #[inline]
     pub fn merge(&mut self, rhs: &Self) {
+        #[cfg(feature = "tracing")]
+        let before = self.representation_kind();
+        self.merge_representations(rhs);
+        #[cfg(feature = "tracing")]
+        self.trace_transition(before);
+    }
+
+    /// Merge `rhs` representation into `self` representation, see `merge`
+    #[inline]
+    fn merge_representations(&mut self, rhs: &Self) {
+        self.invalidate_estimate();
+        // merging with empty estimator is a no-op, while merging into empty
+        // estimator (e.g. neutral element of reduction) is a plain copy
+        if rhs.is_empty() {
+            return;
+        }
+        if self.is_empty() {
+            self.data = rhs.clone_data();
+            return;
+        }
+
         match (self.representation(), rhs.representation()) {
             (_, Representation::Small(rhs_small)) => {
                 for h in rhs_small.items() {
                     if h != 0 {
-                        self.insert_encoded_hash(h);
+                        self.data = self.representation().insert_encoded_hash(h);
+                    }
+                }
+            }
+            (Representation::Array(mut lhs_arr), Representation::Array(rhs_arr)) => {
+                if lhs_arr.merge(&rhs_arr) {
+                    self.data = lhs_arr.to_data();
+                } else {
+                    // union doesn't fit into `Array` representation, so build `HyperLogLog` directly
+                    let mut hll = HyperLogLog::<P, W>::new(&lhs_arr);
+                    for &h in rhs_arr.deref() {
+                        hll.insert_encoded_hash(h);
+                    }
+                    unsafe { lhs_arr.drop() };
+                    self.data = hll.to_data();
+                }
+            }
+            (Representation::Small(lhs_small), Representation::Array(rhs_arr)) => {
+                // build union with capacity of resulting size class at once instead of
+                // growing `self` through intermediate array capacities
+                let mut new_items = [0; SMALL_CAPACITY];
+                let mut new_len = 0;
+                for h in lhs_small.items() {
+                    if h != 0 && !rhs_arr.contains(h) {
+                        new_items[new_len] = h;
+                        new_len += 1;
                     }
                 }
+                let new_items = &new_items[..new_len];
+                let len = rhs_arr.len() + new_items.len();
+                self.data = if len > MAX_CAPACITY {
+                    let mut hll = HyperLogLog::<P, W>::new(&rhs_arr);
+                    for &h in new_items {
+                        hll.insert_encoded_hash(h);
+                    }
+                    hll.to_data()
+                } else {
+                    let mut items = vec![0; len.next_power_of_two()];
+                    items[..rhs_arr.len()].copy_from_slice(&rhs_arr);
+                    items[rhs_arr.len()..len].copy_from_slice(new_items);
+                    Array::<P, W>::from_vec(items, len).to_data()
+                };
             }
             (_, Representation::Array(rhs_arr)) => {
                 for &h in rhs_arr.deref() {
-                    self.insert_encoded_hash(h);
+                    self.data = self.representation().insert_encoded_hash(h);
                 }
             }
+            // exact `self` into `HyperLogLog`: copy of `rhs` registers is the only allocation,
+            // seeded from `self` hashes, rather than upgrading `self` and merging registers
             (Representation::Small(lhs_small), Representation::Hll(rhs_hll)) => {
                 let mut hll = rhs_hll.clone();
                 for h in lhs_small.items() {
@@ -97,110 +668,1291 @@ where
         }
     }
 
-    /// Returns the representation type of `CardinalityEstimator`.
+    /// Consume `CardinalityEstimator` returning set of encoded hashes stored by exact
+    /// representations, or `None` for `HyperLogLog` representation.
+    #[cfg(feature = "std")]
+    pub fn into_hashset(self) -> Option<std::collections::HashSet<u32>> {
+        if matches!(self.representation(), Representation::Hll(_)) {
+            return None;
+        }
+        Some(self.into_iter().collect())
+    }
+
+    /// Return raw `data` word of estimator's representation for `merge_raw`.
+    ///
+    /// Raw word points to memory owned by `self`, so it's only valid while `self` is alive
+    /// and not modified.
     #[inline]
-    pub(crate) fn representation(&self) -> Representation<P, W> {
-        Representation::<P, W>::from_data(self.data)
+    pub fn as_raw(&self) -> usize {
+        self.data
     }
 
-    /// Insert hash into `CardinalityEstimator`
+    /// Merge estimator given by its raw `data` word (see `as_raw`) into `self`, e.g. estimator
+    /// of a different type whose build hasher produces the same hashes as `self` build hasher.
+    ///
+    /// Estimators using the same hasher can be merged safely with `merge`, or across types
+    /// with `merge_from_bytes`, which is preferred unless avoiding serialization is critical.
+    ///
+    /// # Safety
+    /// `data` must be returned by `as_raw` of an estimator with the same `P` and `W`
+    /// parameters, which stays alive and unmodified during the call. Caller must guarantee
+    /// that both estimators hash items identically, otherwise merged estimate is meaningless.
+    pub unsafe fn merge_raw(&mut self, data: usize)
+    where
+        S: Clone,
+    {
+        // `rhs` borrows representation owned by another estimator, so it must never be dropped
+        let mut rhs = ManuallyDrop::new(Self::with_hasher_unchecked(self.build_hasher.clone()));
+        rhs.data = data;
+        self.merge(&rhs);
+        rhs.data = 0;
+        drop(ManuallyDrop::into_inner(rhs));
+    }
+
+    /// Merge owned `rhs` into `self` reusing its allocation where possible.
+    ///
+    /// If `self` is empty, or `self` is exact while `rhs` is in `HyperLogLog` representation,
+    /// representations are swapped, so `rhs` registers are moved instead of copied and
+    /// previous `self` hashes are merged into them. Otherwise it's equivalent to `merge`.
     #[inline]
-    pub fn insert_hash(&mut self, hash: u64) {
-        self.insert_encoded_hash(Self::encode_hash(hash));
+    pub fn merge_owned(&mut self, mut rhs: Self) {
+        #[cfg(feature = "tracing")]
+        let before = self.representation_kind();
+        let steal = self.is_empty()
+            || (!matches!(self.representation(), Representation::Hll(_))
+                && matches!(rhs.representation(), Representation::Hll(_)));
+        if steal {
+            self.invalidate_estimate();
+            core::mem::swap(&mut self.data, &mut rhs.data);
+        }
+        self.merge_representations(&rhs);
+        #[cfg(feature = "tracing")]
+        self.trace_transition(before);
+    }
+
+    /// Merge owned `rhs` into `self` returning its freed backing buffer, e.g. for reducers
+    /// pooling allocations of merged estimators.
+    ///
+    /// Returned vector is empty, with capacity of `rhs` heap allocation: `Array` capacity or
+    /// `HyperLogLog` slice length. `None` is returned if `rhs` used inline `Small` representation.
+    pub fn merge_take_buffer(&mut self, mut rhs: Self) -> Option<Vec<u32>> {
+        self.merge(&rhs);
+        let mut buffer = match rhs.representation() {
+            Representation::Small(_) => return None,
+            // SAFETY: ownership of array is transferred to returned vector
+            Representation::Array(arr) => unsafe { arr.into_vec() },
+            // SAFETY: `HyperLogLog` slice is always allocated as boxed slice of exactly
+            // `HLL_SLICE_LEN` elements, including deserialized ones, whose ownership is
+            // transferred to returned vector
+            Representation::Hll(hll) => unsafe { Box::from_raw(hll.data).into_vec() },
+        };
+        // `rhs` no longer owns its allocation, so reset it to empty `Small` representation
+        rhs.data = 0;
+        buffer.clear();
+        Some(buffer)
     }
 
-    /// Insert encoded hash into `CardinalityEstimator`
+    /// Return rough number of operations needed to merge `rhs` into `self`, e.g. for
+    /// schedulers batching cheap merges together.
+    ///
+    /// Merging exact `rhs` costs one insert per its encoded hash, while merging `HyperLogLog`
+    /// `rhs` costs `2^P` register updates plus one insert per encoded hash of exact `self`.
     #[inline]
-    fn insert_encoded_hash(&mut self, h: u32) {
-        self.data = self.representation().insert_encoded_hash(h);
+    pub fn merge_cost(&self, rhs: &Self) -> usize {
+        match (self.representation(), rhs.representation()) {
+            (_, Representation::Small(_) | Representation::Array(_)) => rhs.estimate(),
+            (Representation::Hll(_), Representation::Hll(_)) => HyperLogLog::<P, W>::M,
+            (lhs, Representation::Hll(_)) => HyperLogLog::<P, W>::M + lhs.estimate(),
+        }
+    }
+
+    /// Merge `rhs` into `self` returning number of `rhs` encoded hashes added to `self` and
+    /// number of those already stored by `self`, e.g. to gauge hash collision rates of data.
+    ///
+    /// Report is only available if both estimators are in exact representations, otherwise
+    /// estimators are merged and `None` is returned.
+    pub fn merge_with_report(&mut self, rhs: &Self) -> Option<MergeReport> {
+        let collisions = match (self.representation(), rhs.representation()) {
+            (Representation::Hll(_), _) | (_, Representation::Hll(_)) => None,
+            (_, Representation::Small(small)) => Some(
+                small
+                    .items()
+                    .iter()
+                    .filter(|&&h| h != 0 && self.contains_encoded_hash(h))
+                    .count(),
+            ),
+            (_, Representation::Array(arr)) => Some(
+                arr.iter()
+                    .filter(|&&h| self.contains_encoded_hash(h))
+                    .count(),
+            ),
+        };
+        let report = collisions.map(|collisions| MergeReport {
+            added: rhs.estimate() - collisions,
+            collisions,
+        });
+        self.merge(rhs);
+        report
     }
 
-    /// Compute the sparse encoding of the given hash
+    /// Merge `rhs` into `self` returning increase of cardinality estimate caused by the merge.
+    ///
+    /// Delta is usually non-negative, but may be negative when merge converts exact
+    /// representation into approximate `HyperLogLog` representation.
     #[inline]
-    fn encode_hash(hash: u64) -> u32 {
-        let idx = (hash as u32) & ((1 << (32 - W - 1)) - 1);
-        let rank = (!hash >> P).trailing_zeros() + 1;
-        (idx << W) | rank
+    pub fn merge_returning_delta(&mut self, rhs: &Self) -> isize {
+        let old_estimate = self.estimate() as isize;
+        self.merge(rhs);
+        self.estimate() as isize - old_estimate
     }
 
-    /// Return memory size of `CardinalityEstimator`
-    pub fn size_of(&self) -> usize {
-        self.representation().size_of()
+    /// Merge estimator with different register width `W2` into `self`.
+    ///
+    /// Encoded hashes of exact representations store `31 - W2` hash bits and `W2`-bit rank, so
+    /// for `W2 <= W` stored encoded hashes are losslessly re-encoded and remain exact. Their
+    /// ranks were already saturated at `2^W2 - 1` by `encode_hash` of `rhs` though, so they
+    /// stay truncated and registers may end up lower than if items were inserted into `self`.
+    /// For `W2 > W` encoded hashes lack hash bits, so `self` is converted into `HyperLogLog`
+    /// representation and only their registers are merged. Register ranks of `rhs` exceeding
+    /// `2^W - 1` are saturated.
+    pub fn merge_rewidth<const W2: usize>(&mut self, rhs: &CardinalityEstimator<T, S, P, W2>) {
+        #[cfg(feature = "tracing")]
+        let before = self.representation_kind();
+        self.invalidate_estimate();
+        let max_rank = (1u32 << W) - 1;
+        let rhs_representation = rhs.representation();
+        let hashes: Vec<u32> = match &rhs_representation {
+            Representation::Hll(hll) => (0..HyperLogLog::<P, W2>::M as u32)
+                .map(|idx| (idx << W) | hll.get_register(idx).min(max_rank))
+                .filter(|&h| h & max_rank != 0)
+                .collect(),
+            Representation::Small(small) => small.items().into_iter().filter(|&h| h != 0).collect(),
+            Representation::Array(arr) => arr.to_vec(),
+        };
+        let hashes = if matches!(rhs_representation, Representation::Hll(_)) {
+            self.upgrade_to_hll();
+            hashes
+        } else if W2 <= W {
+            hashes
+                .into_iter()
+                .map(|h| (((h >> W2) & ((1 << (31 - W)) - 1)) << W) | (h & ((1 << W2) - 1)))
+                .collect()
+        } else {
+            self.upgrade_to_hll();
+            hashes
+                .into_iter()
+                .map(|h| {
+                    let (idx, rank) = HyperLogLog::<P, W2>::decode_hash(h);
+                    (idx << W) | rank.min(max_rank)
+                })
+                .collect()
+        };
+        for h in hashes {
+            self.data = self.representation().insert_encoded_hash(h);
+        }
+        #[cfg(feature = "tracing")]
+        self.trace_transition(before);
     }
-}
 
-impl<T, H, const P: usize, const W: usize> Default for CardinalityEstimator<T, H, P, W>
-where
-    T: Hash + ?Sized,
-    H: Hasher + Default,
-{
-    fn default() -> Self {
-        Self::new()
+    /// Merge `rhs` into `self` unless memory size of resulting estimator would exceed
+    /// `max_bytes`, in which case `CapExceeded` is returned and `self` is left unchanged.
+    pub fn merge_capped(&mut self, rhs: &Self, max_bytes: usize) -> Result<(), CapExceeded> {
+        let size = self.merged_size_of(rhs);
+        if size > max_bytes {
+            return Err(CapExceeded { size, max_bytes });
+        }
+        self.merge(rhs);
+        Ok(())
     }
-}
 
-impl<T, H, const P: usize, const W: usize> Clone for CardinalityEstimator<T, H, P, W>
-where
-    T: Hash + ?Sized,
-    H: Hasher + Default,
-{
-    /// Clone `CardinalityEstimator`
-    fn clone(&self) -> Self {
+    /// Predict memory size of `self` after merging `rhs` into it without merging them
+    fn merged_size_of(&self, rhs: &Self) -> usize {
+        let rhs_small_items;
+        let rhs_representation = rhs.representation();
+        let rhs_items: &[u32] = match &rhs_representation {
+            Representation::Small(small) => {
+                rhs_small_items = small.items();
+                &rhs_small_items
+            }
+            Representation::Array(arr) => arr,
+            Representation::Hll(hll) => return hll.size_of(),
+        };
+        let len = match self.representation() {
+            Representation::Small(small) => {
+                let items = small.items();
+                let new = rhs_items.iter().filter(|&&h| h != 0 && !items.contains(&h));
+                self.estimate() + new.count()
+            }
+            Representation::Array(arr) => {
+                self.estimate() + rhs_items.iter().filter(|&&h| !arr.contains(h)).count()
+            }
+            Representation::Hll(hll) => return hll.size_of(),
+        };
+        if len <= SMALL_CAPACITY {
+            size_of::<usize>()
+        } else if len <= MAX_CAPACITY {
+            size_of::<usize>() + len.next_power_of_two() * size_of::<u32>()
+        } else {
+            size_of::<usize>() + HyperLogLog::<P, W>::HLL_SLICE_LEN * size_of::<u32>()
+        }
+    }
+
+    /// Return new estimator representing union of `self` and `rhs` without mutating either.
+    ///
+    /// Operand with the larger representation is cloned and the other one is merged into it,
+    /// which avoids intermediate representation upgrades of the copy.
+    pub fn merged(&self, rhs: &Self) -> Self
+    where
+        S: Clone,
+    {
+        let rank = |e: &Self| {
+            let order = match e.representation_kind() {
+                RepresentationKind::Small => 0,
+                RepresentationKind::Array => 1,
+                RepresentationKind::Hll => 2,
+            };
+            (order, e.estimate())
+        };
+        let (larger, smaller) = if rank(rhs) > rank(self) {
+            (rhs, self)
+        } else {
+            (self, rhs)
+        };
+        let mut union = larger.clone();
+        union.merge(smaller);
+        union
+    }
+
+    /// Merge all given cardinality estimators into `self`, e.g. into existing accumulator
+    #[inline]
+    pub fn merge_many<'a, I>(&mut self, others: I)
+    where
+        I: IntoIterator<Item = &'a Self>,
+        Self: 'a,
+    {
+        for rhs in others {
+            self.merge(rhs);
+        }
+    }
+
+    /// Merge all given cardinality estimators into a new one
+    #[inline]
+    pub fn merge_all<'a, I>(estimators: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Self>,
+        Self: 'a,
+        S: Default,
+    {
         let mut estimator = Self::new();
-        estimator.merge(self);
+        for rhs in estimators {
+            estimator.merge(rhs);
+        }
         estimator
     }
-}
 
-impl<T, H, const P: usize, const W: usize> Drop for CardinalityEstimator<T, H, P, W>
-where
-    T: Hash + ?Sized,
-    H: Hasher + Default,
-{
-    /// Free memory occupied by `CardinalityEstimator`
+    /// Merge all given cardinality estimators into a new one, skipping encoded hashes which
+    /// were already merged from previous estimators.
+    ///
+    /// This is an alternative to `merge_all` for reducing many exact estimators with heavily
+    /// overlapping items (see `merge_all` benchmark group): merged hashes are tracked by a small Bloom filter, and
+    /// hashes reported by it as seen are verified against merged estimator instead of being
+    /// re-inserted. Result is identical to `merge_all`.
+    pub fn merge_all_prefiltered<'a, I>(estimators: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Self>,
+        Self: 'a,
+        S: Default,
+    {
+        let mut estimator = Self::new();
+        let mut seen = BloomFilter::new();
+        for rhs in estimators {
+            // once merged estimator is `HyperLogLog`, inserts are cheap register updates
+            if matches!(estimator.representation(), Representation::Hll(_)) {
+                estimator.merge(rhs);
+                continue;
+            }
+            let rhs_representation = rhs.representation();
+            let small_items;
+            let items: &[u32] = match &rhs_representation {
+                Representation::Small(small) => {
+                    small_items = small.items();
+                    &small_items
+                }
+                Representation::Array(arr) => arr,
+                Representation::Hll(_) => {
+                    estimator.merge(rhs);
+                    continue;
+                }
+            };
+            #[cfg(feature = "tracing")]
+            let before = estimator.representation_kind();
+            for &h in items.iter().filter(|&&h| h != 0) {
+                if seen.may_contain(h) && estimator.contains_encoded_hash(h) {
+                    continue;
+                }
+                seen.insert(h);
+                estimator.data = estimator.representation().insert_encoded_hash(h);
+            }
+            #[cfg(feature = "tracing")]
+            estimator.trace_transition(before);
+        }
+        estimator
+    }
+
+    /// Return true if encoded hash is stored in exact representation,
+    /// `HyperLogLog` representation always returns false.
     #[inline]
-    fn drop(&mut self) {
-        unsafe { self.representation().drop() };
+    fn contains_encoded_hash(&self, h: u32) -> bool {
+        match self.representation() {
+            Representation::Small(small) => small.items().contains(&h),
+            Representation::Array(arr) => arr.contains(h),
+            Representation::Hll(_) => false,
+        }
     }
-}
 
-impl<T, H, const P: usize, const W: usize> PartialEq for CardinalityEstimator<T, H, P, W>
-where
-    T: Hash + ?Sized,
-    H: Hasher + Default,
-{
-    /// Compare cardinality estimators
-    fn eq(&self, rhs: &Self) -> bool {
-        self.representation() == rhs.representation()
+    /// Return cardinality estimate of union of `self` and `rhs` without modifying either
+    #[inline]
+    pub fn union_estimate(&self, rhs: &Self) -> usize
+    where
+        S: Clone,
+    {
+        self.union_with(rhs).estimate()
     }
-}
 
-impl<T, H, const P: usize, const W: usize> Debug for CardinalityEstimator<T, H, P, W>
-where
-    T: Hash + ?Sized,
-    H: Hasher + Default,
-{
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?}", self.representation())
+    /// Return lightweight view of union of `self` and `rhs`, which estimates union cardinality
+    /// lazily without modifying or cloning `HyperLogLog` representations of either.
+    #[inline]
+    pub fn union_with<'a>(&'a self, rhs: &'a Self) -> UnionView<'a, T, S, P, W> {
+        UnionView { lhs: self, rhs }
     }
-}
 
-#[cfg(test)]
-pub mod tests {
-    use super::*;
-    use test_case::test_case;
+    /// Return Jaccard similarity estimate of `self` and `rhs` in [0.0..1.0] range.
+    ///
+    /// Intersection size is estimated using inclusion-exclusion principle
+    /// `|A ∩ B| = |A| + |B| - |A ∪ B|`. Two empty estimators are considered identical.
+    #[inline]
+    pub fn jaccard(&self, rhs: &Self) -> f64
+    where
+        S: Clone,
+    {
+        let union = self.union_estimate(rhs);
+        if union == 0 {
+            return 1.0;
+        }
+        let intersection = (self.estimate() + rhs.estimate()).saturating_sub(union);
+        (intersection as f64 / union as f64).min(1.0)
+    }
 
-    #[test_case(0 => "representation: Small(estimate: 0, size: 8), avg_err: 0.0000")]
-    #[test_case(1 => "representation: Small(estimate: 1, size: 8), avg_err: 0.0000")]
-    #[test_case(2 => "representation: Small(estimate: 2, size: 8), avg_err: 0.0000")]
-    #[test_case(3 => "representation: Array(estimate: 3, size: 24), avg_err: 0.0000")]
-    #[test_case(4 => "representation: Array(estimate: 4, size: 24), avg_err: 0.0000")]
-    #[test_case(8 => "representation: Array(estimate: 8, size: 40), avg_err: 0.0000")]
-    #[test_case(16 => "representation: Array(estimate: 16, size: 72), avg_err: 0.0000")]
-    #[test_case(17 => "representation: Array(estimate: 17, size: 136), avg_err: 0.0000")]
-    #[test_case(28 => "representation: Array(estimate: 28, size: 136), avg_err: 0.0000")]
-    #[test_case(29 => "representation: Array(estimate: 29, size: 136), avg_err: 0.0000")]
-    #[test_case(56 => "representation: Array(estimate: 56, size: 264), avg_err: 0.0000")]
+    /// Return `true` if `self` and `rhs` estimate approximately the same set of items,
+    /// i.e. their Jaccard similarity estimate is at least `1.0 - tolerance`.
+    #[inline]
+    pub fn approx_eq(&self, rhs: &Self, tolerance: f64) -> bool
+    where
+        S: Clone,
+    {
+        self.jaccard(rhs) >= 1.0 - tolerance
+    }
+
+    /// Collapse near-duplicate estimators by greedily merging each estimator into the first
+    /// already kept one whose Jaccard similarity with it exceeds `jaccard_threshold`.
+    ///
+    /// Each estimator is compared with all kept ones, so this is O(n^2) in the worst case.
+    pub fn dedup_sketches(sketches: Vec<Self>, jaccard_threshold: f64) -> Vec<Self>
+    where
+        S: Clone,
+    {
+        let mut kept: Vec<Self> = Vec::with_capacity(sketches.len());
+        for sketch in sketches {
+            match kept
+                .iter_mut()
+                .find(|k| k.jaccard(&sketch) > jaccard_threshold)
+            {
+                Some(k) => k.merge(&sketch),
+                None => kept.push(sketch),
+            }
+        }
+        kept
+    }
+
+    /// Return cardinality estimate of intersection of given estimators.
+    ///
+    /// Intersection size is computed using k-way inclusion-exclusion principle over unions of all
+    /// non-empty subsets of estimators, which is a baseline approach: estimation errors of `2^k - 1`
+    /// unions add up while intersection itself may be much smaller than them, so relative error
+    /// grows combinatorially with `k`. Therefore at most `MAX_INTERSECTION_SKETCHES` estimators are
+    /// supported, returning `IntersectionError::TooManySketches` otherwise.
+    pub fn intersection_estimate_many(sketches: &[&Self]) -> Result<usize, IntersectionError>
+    where
+        S: Default,
+    {
+        if sketches.len() > MAX_INTERSECTION_SKETCHES {
+            return Err(IntersectionError::TooManySketches);
+        }
+        if sketches.is_empty() {
+            return Ok(0);
+        }
+
+        let mut intersection: i64 = 0;
+        for mask in 1u32..(1 << sketches.len()) {
+            let union = Self::merge_all(
+                sketches
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| mask & (1 << i) != 0)
+                    .map(|(_, &sketch)| sketch),
+            );
+            let sign = if mask.count_ones() % 2 == 1 { 1 } else { -1 };
+            intersection += sign * union.estimate() as i64;
+        }
+
+        // intersection can't be larger than the smallest of estimators
+        let upper = sketches.iter().map(|s| s.estimate()).min().unwrap_or(0);
+        Ok((intersection.max(0) as usize).min(upper))
+    }
+
+    /// Recompute number of zero registers and registers' harmonic sum of `HyperLogLog`
+    /// representation from scratch, e.g. to discard floating point drift accumulated by
+    /// incremental updates at checkpoints of long insert runs. Harmonic sum is recomputed in
+    /// `f64` precision, while registers are unchanged. This is a no-op for other representations.
+    #[inline]
+    pub fn recompute_accounting(&mut self) {
+        self.invalidate_estimate();
+        if let Representation::Hll(mut hll) = self.representation() {
+            hll.recompute_from_histogram();
+        }
+    }
+
+    /// Compact `HyperLogLog` accounting at checkpoints of long insert runs, resetting
+    /// floating point drift of harmonic sum without changing registers,
+    /// see `recompute_accounting`.
+    #[inline]
+    pub fn compact(&mut self) {
+        self.recompute_accounting();
+    }
+
+    /// Reserve capacity for at least `additional` more distinct items.
+    ///
+    /// Exact representations grow cheaply by doubling, while `Array` capacity always follows
+    /// its length, so this only takes effect when resulting cardinality would exceed `Array`
+    /// representation capacity: in that case `HyperLogLog` representation is allocated
+    /// upfront, skipping intermediate `Array` growth.
+    ///
+    /// `additional` must count distinct items only, e.g. known from upstream deduplication,
+    /// as reserving switches to approximate representation. Iterator size hints count
+    /// duplicates too, so `FromIterator` and `Extend` don't reserve.
+    #[inline]
+    pub fn reserve(&mut self, additional: usize) {
+        if self.estimate().saturating_add(additional) > MAX_CAPACITY {
+            #[cfg(feature = "tracing")]
+            let before = self.representation_kind();
+            self.upgrade_to_hll();
+            #[cfg(feature = "tracing")]
+            self.trace_transition(before);
+        }
+    }
+
+    /// Convert `CardinalityEstimator` into `HyperLogLog` representation
+    /// (no-op if it's already in `HyperLogLog` representation).
+    pub(crate) fn upgrade_to_hll(&mut self) {
+        self.invalidate_estimate();
+        self.data = match self.representation() {
+            Representation::Small(small) => {
+                let mut hll = HyperLogLog::<P, W>::new(&[]);
+                for h in small.items() {
+                    if h != 0 {
+                        hll.insert_encoded_hash(h);
+                    }
+                }
+                hll.to_data()
+            }
+            Representation::Array(mut arr) => {
+                let hll = HyperLogLog::<P, W>::new(&arr);
+                unsafe { arr.drop() };
+                hll.to_data()
+            }
+            Representation::Hll(hll) => hll.to_data(),
+        };
+    }
+
+    /// Reset estimator to empty `HyperLogLog` representation freeing its current representation,
+    /// e.g. to keep pooled estimators at predictable memory size.
+    pub fn reset_to_hyperloglog(&mut self) {
+        #[cfg(feature = "tracing")]
+        let before = self.representation_kind();
+        self.invalidate_estimate();
+        // allocate new representation before freeing current one, so that `data` never
+        // points to freed memory if allocation unwinds
+        let data = HyperLogLog::<P, W>::new(&[]).to_data();
+        unsafe { self.representation().drop() };
+        self.data = data;
+        #[cfg(feature = "tracing")]
+        self.trace_transition(before);
+    }
+
+    /// Create `CardinalityEstimator` in `HyperLogLog` representation from `2^P` register ranks,
+    /// e.g. exported by `CardinalityEstimator` with the same `P` and hasher.
+    ///
+    /// Returns `RegisterError` if number of registers isn't `2^P` or if some register's
+    /// rank doesn't fit into `W` bits.
+    pub fn from_registers(registers: &[u32]) -> Result<Self, RegisterError>
+    where
+        S: Default,
+    {
+        if registers.len() != HyperLogLog::<P, W>::M {
+            return Err(RegisterError::InvalidLength(registers.len()));
+        }
+        if let Some(idx) = registers.iter().position(|&rank| rank >= 1 << W) {
+            return Err(RegisterError::InvalidRank {
+                idx,
+                rank: registers[idx],
+            });
+        }
+
+        let mut hll = HyperLogLog::<P, W>::new(&[]);
+        for (idx, &rank) in registers.iter().enumerate() {
+            if rank != 0 {
+                hll.insert_encoded_hash(((idx as u32) << W) | rank);
+            }
+        }
+        let mut estimator = Self::new();
+        estimator.data = hll.to_data();
+        Ok(estimator)
+    }
+
+    /// Set register `idx` to `rank` in `HyperLogLog` representation updating number of zero
+    /// registers and harmonic sum, e.g. to build sketch incrementally from external registers.
+    ///
+    /// Registers only grow, so register already holding larger rank is left unchanged.
+    /// Returns `RegisterError` if `idx` isn't in `[0..2^P)` range, `rank` doesn't fit into
+    /// `W` bits or estimator isn't in `HyperLogLog` representation (see `from_registers`).
+    pub fn set_register_checked(&mut self, idx: u32, rank: u32) -> Result<(), RegisterError> {
+        if idx as usize >= HyperLogLog::<P, W>::M {
+            return Err(RegisterError::InvalidIndex(idx as usize));
+        }
+        if rank >= 1 << W {
+            return Err(RegisterError::InvalidRank {
+                idx: idx as usize,
+                rank,
+            });
+        }
+        let Representation::Hll(mut hll) = Representation::<P, W>::from_data(self.data) else {
+            return Err(RegisterError::NotHyperLogLog);
+        };
+        self.invalidate_estimate();
+        hll.update_rank(idx, rank);
+        Ok(())
+    }
+
+    /// Verify internal consistency of `CardinalityEstimator` representation,
+    /// e.g. to be used as a cheap check while fuzzing.
+    #[inline]
+    pub fn verify_invariants(&self) -> Result<(), InvariantError> {
+        self.representation().verify_invariants()
+    }
+
+    /// Return `data` of a deep copy of estimator's representation
+    #[inline]
+    fn clone_data(&self) -> usize {
+        match self.representation() {
+            Representation::Small(small) => small.to_data(),
+            Representation::Array(arr) => arr.clone().to_data(),
+            Representation::Hll(hll) => hll.clone().to_data(),
+        }
+    }
+
+    /// Returns the representation type of `CardinalityEstimator`.
+    #[inline]
+    pub(crate) fn representation(&self) -> Representation<'_, P, W> {
+        Representation::<P, W>::from_data(self.data)
+    }
+
+    /// Insert hash into `CardinalityEstimator`
+    #[inline]
+    pub fn insert_hash(&mut self, hash: u64) {
+        self.insert_encoded(Self::encode_hash(hash));
+    }
+
+    /// Insert precomputed 64-bit hash (e.g. obtained from an external system) into
+    /// `CardinalityEstimator` without hashing it again.
+    ///
+    /// Caller is responsible for hash quality: all 64 bits of the hash must be uniformly
+    /// distributed (have good avalanche properties), otherwise estimates will be biased.
+    #[inline]
+    pub fn insert_prehashed(&mut self, hash: u64) {
+        self.insert_hash(hash);
+    }
+
+    /// Insert hashes sorted by their register index, i.e. by their lowest `P` bits, into
+    /// `CardinalityEstimator`.
+    ///
+    /// This is equivalent to calling `insert_hash` for each hash (regardless of their order),
+    /// but once estimator is in `HyperLogLog` representation, consecutive hashes update
+    /// registers in index order, so packed registers are accessed sequentially and stay in cache.
+    /// Hashes can be sorted with `sort_unstable_by_key(|&hash| hash & ((1 << P) - 1))`.
+    pub fn insert_hashes_sorted(&mut self, sorted_hashes: &[u64]) {
+        debug_assert!(
+            sorted_hashes
+                .windows(2)
+                .all(|w| w[0] & ((1 << P) - 1) <= w[1] & ((1 << P) - 1)),
+            "hashes must be sorted by register index"
+        );
+        let mut hashes = sorted_hashes.iter();
+        while !matches!(self.representation(), Representation::Hll(_)) {
+            match hashes.next() {
+                Some(&hash) => self.insert_hash(hash),
+                None => return,
+            }
+        }
+        self.invalidate_estimate();
+        let Representation::Hll(mut hll) = Representation::<P, W>::from_data(self.data) else {
+            unreachable!("estimator must be in HyperLogLog representation");
+        };
+        for &hash in hashes.as_slice() {
+            hll.insert_encoded_hash(Self::encode_hash(hash));
+            #[cfg(feature = "recompute")]
+            self.recompute.on_insert(&mut hll);
+        }
+        #[cfg(feature = "stats")]
+        {
+            self.stats.inserts += hashes.len() as u64;
+        }
+    }
+
+    /// Emit trace event on transition from `before` representation, e.g. upgrade to
+    /// `HyperLogLog` representation, with resulting representation and memory size
+    /// (no-op if representation is same).
+    #[cfg(feature = "tracing")]
+    fn trace_transition(&self, before: RepresentationKind) {
+        let after = self.representation_kind();
+        if before != after {
+            tracing::trace!(
+                from = before.as_str(),
+                to = after.as_str(),
+                size = self.size_of(),
+                "cardinality estimator representation changed"
+            );
+        }
+    }
+
+    /// Insert encoded hash produced by `encode_hash` into `CardinalityEstimator`.
+    ///
+    /// # Panics
+    /// Panics if `h` is not a valid encoded hash, i.e. it has zero rank or uses more than 31 bits.
+    #[inline]
+    pub fn insert_encoded_hash(&mut self, h: u32) {
+        assert!(
+            h >> 31 == 0 && h & ((1 << W) - 1) != 0,
+            "invalid encoded hash: {h:#x}"
+        );
+        self.insert_encoded(h);
+    }
+
+    /// Insert valid encoded hash into `CardinalityEstimator` updating insertion statistics.
+    ///
+    /// With `tracing` feature enabled representation upgrades are emitted as `trace!` events,
+    /// otherwise no tracing code is compiled.
+    #[inline]
+    fn insert_encoded(&mut self, h: u32) {
+        self.invalidate_estimate();
+        #[cfg(feature = "stats")]
+        let before = (self.representation_kind(), self.estimate_exact());
+        #[cfg(feature = "tracing")]
+        let before_kind = self.representation_kind();
+        self.data = self.representation().insert_encoded_hash(h);
+        #[cfg(feature = "tracing")]
+        self.trace_transition(before_kind);
+        #[cfg(feature = "stats")]
+        self.stats
+            .record(before, (self.representation_kind(), self.estimate_exact()));
+        #[cfg(feature = "recompute")]
+        if let Representation::Hll(mut hll) = Representation::<P, W>::from_data(self.data) {
+            self.recompute.on_insert(&mut hll);
+        }
+    }
+
+    /// Set number of inserts into `HyperLogLog` representation after which its harmonic sum,
+    /// maintained incrementally in `f32` precision, is recomputed from registers.
+    ///
+    /// This bounds accumulated floating point drift of the sum at the cost of scanning all
+    /// registers once per `interval` inserts. Zero interval (default) disables recomputing.
+    #[cfg(feature = "recompute")]
+    pub fn set_recompute_interval(&mut self, interval: u32) {
+        self.recompute = RecomputeInterval {
+            interval,
+            inserts: 0,
+        };
+    }
+
+    /// Return insertion statistics if `stats` feature is enabled, `None` otherwise.
+    ///
+    /// Statistics count items and hashes inserted via `insert*` methods, while
+    /// items added by merging other estimators aren't counted.
+    #[inline]
+    pub fn stats(&self) -> Option<InsertStats> {
+        #[cfg(feature = "stats")]
+        return Some(self.stats);
+        #[cfg(not(feature = "stats"))]
+        None
+    }
+
+    /// Compute the sparse encoding of the given 64-bit hash.
+    ///
+    /// Encoded hash uses 31 bits and depends on both `P` and `W` parameters:
+    /// - 0..W bits     - store rank, i.e. number of trailing zeros of `!hash >> P` plus one,
+    ///   saturated at the largest rank `2^W - 1` representable by a register
+    /// - W..31 bits    - store `31 - W` lowest bits of the hash, the lowest `P` of which
+    ///   are the HyperLogLog register index
+    ///
+    /// Encoded hashes are stored as is by exact representations and are decoded
+    /// into HyperLogLog registers when estimator is upgraded to `HyperLogLog` representation.
+    #[inline]
+    pub fn encode_hash(hash: u64) -> u32 {
+        let idx = (hash as u32) & ((1 << (32 - W - 1)) - 1);
+        let rank = ((!hash >> P).trailing_zeros() + 1).min((1 << W) - 1);
+        (idx << W) | rank
+    }
+
+    /// Return number of hash bits kept by `encode_hash` in exact representations, i.e. `31 - W`.
+    ///
+    /// Distinct items are only counted as duplicates by exact representations if their hashes
+    /// collide in these bits. The width isn't configurable on its own: encoded hashes must fit
+    /// into the 31-bit slots of `Small` representation next to the `W`-bit rank, so it's fixed
+    /// by register width `W`, which also bounds `HyperLogLog` register ranks at `2^W - 1`.
+    #[inline]
+    pub const fn sparse_index_bits() -> usize {
+        31 - W
+    }
+
+    /// Decode encoded hash produced by `encode_hash` into HyperLogLog register
+    /// `(index, rank)` pair, where index is in `[0..2^P)` range.
+    #[inline]
+    pub fn decode_hash(h: u32) -> (u32, u32) {
+        HyperLogLog::<P, W>::decode_hash(h)
+    }
+
+    /// Return number of bits effectively used to store current representation's data.
+    ///
+    /// Unlike `size_of`, this doesn't include unused capacity, pointers or allocation granularity:
+    /// - `Small` representation: 31 bits per stored encoded hash;
+    /// - `Array` representation: 32 bits per stored encoded hash;
+    /// - `HyperLogLog` representation: `W` bits per each of `2^P` registers plus 64 bits for
+    ///   number of zero registers and registers' harmonic sum.
+    #[inline]
+    pub fn num_bits_used(&self) -> usize {
+        match self.representation() {
+            Representation::Small(small) => small.estimate() * 31,
+            Representation::Array(arr) => arr.len() * 32,
+            Representation::Hll(_) => (1 << P) * W + 64,
+        }
+    }
+
+    /// Return maximum number of distinct items kept in the inline `Small` representation.
+    ///
+    /// `Small` representation packs encoded hashes together with the 2-bit representation tag
+    /// into a single 8-byte word, so it can hold at most two 31-bit hashes. Larger cardinalities
+    /// are stored in heap allocated `Array` representation starting from 24 bytes.
+    #[inline]
+    pub const fn small_capacity() -> usize {
+        Self::SMALL_MAX
+    }
+
+    /// Return number of `u32` words in the dense `HyperLogLog` slice for parameters `P` and `W`.
+    ///
+    /// Slice stores the number of zero registers, the `f32` harmonic sum of registers,
+    /// `2^P` registers packed into `W` bits each and one trailing zero word, so that a register
+    /// spanning two words can always be read with a single `u64` load.
+    #[inline]
+    pub const fn hll_slice_len() -> usize {
+        HyperLogLog::<P, W>::HLL_SLICE_LEN
+    }
+
+    /// Return memory size of `CardinalityEstimator`
+    pub fn size_of(&self) -> usize {
+        self.representation().size_of()
+    }
+}
+
+/// Lazy view of union of two cardinality estimators returned by `CardinalityEstimator::union_with`
+pub struct UnionView<'a, T, S, const P: usize, const W: usize>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    lhs: &'a CardinalityEstimator<T, S, P, W>,
+    rhs: &'a CardinalityEstimator<T, S, P, W>,
+}
+
+impl<T, S, const P: usize, const W: usize> UnionView<'_, T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher + Clone,
+{
+    /// Return cardinality estimate of union.
+    ///
+    /// Union of two `HyperLogLog` representations is estimated by iterating both registers on
+    /// demand, while union involving exact representations is materialized by merging a clone.
+    #[inline]
+    pub fn estimate(&self) -> usize {
+        match (self.lhs.representation(), self.rhs.representation()) {
+            (Representation::Hll(lhs_hll), Representation::Hll(rhs_hll)) => {
+                lhs_hll.union_estimate(&rhs_hll)
+            }
+            _ => {
+                let mut union = self.lhs.clone();
+                union.merge(self.rhs);
+                union.estimate()
+            }
+        }
+    }
+}
+
+impl<S, const P: usize, const W: usize> CardinalityEstimator<[u8], S, P, W>
+where
+    S: BuildHasher,
+{
+    /// Insert byte slice records into `CardinalityEstimator`, e.g. log lines.
+    ///
+    /// This is equivalent to calling `insert` for each record, but once estimator is in
+    /// `HyperLogLog` representation the representation is resolved only once
+    /// for all remaining records.
+    pub fn insert_byte_records<'a, I>(&mut self, records: I)
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+    {
+        let mut records = records.into_iter();
+        while !matches!(self.representation(), Representation::Hll(_)) {
+            match records.next() {
+                Some(record) => self.insert(record),
+                None => return,
+            }
+        }
+        self.invalidate_estimate();
+        let Representation::Hll(mut hll) = Representation::<P, W>::from_data(self.data) else {
+            unreachable!("estimator must be in HyperLogLog representation");
+        };
+        #[cfg(feature = "stats")]
+        let mut inserted = 0;
+        for record in records {
+            let hash = self.build_hasher.hash_one(record);
+            hll.insert_encoded_hash(Self::encode_hash(hash));
+            #[cfg(feature = "recompute")]
+            self.recompute.on_insert(&mut hll);
+            #[cfg(feature = "stats")]
+            {
+                inserted += 1;
+            }
+        }
+        #[cfg(feature = "stats")]
+        {
+            self.stats.inserts += inserted;
+        }
+    }
+}
+
+/// Maximum number of estimators supported by `CardinalityEstimator::intersection_estimate_many`
+pub const MAX_INTERSECTION_SKETCHES: usize = 4;
+
+/// Intersection estimation error
+#[derive(Debug, PartialEq)]
+pub enum IntersectionError {
+    /// More than `MAX_INTERSECTION_SKETCHES` estimators were given
+    TooManySketches,
+}
+
+/// Parameters validation error returned by `CardinalityEstimator::checked_new`
+#[derive(Debug, PartialEq)]
+pub enum ParamError {
+    /// Precision `P` is out of `[4..18]` range
+    InvalidPrecision(usize),
+    /// Register width `W` is out of `[4..6]` range
+    InvalidWidth(usize),
+}
+
+/// Register validation error returned by `CardinalityEstimator::from_registers`
+/// and `CardinalityEstimator::set_register_checked`
+#[derive(Debug, PartialEq)]
+pub enum RegisterError {
+    /// Number of registers isn't equal to `2^P`
+    InvalidLength(usize),
+    /// Register `idx` has rank which doesn't fit into `W` bits
+    InvalidRank { idx: usize, rank: u32 },
+    /// Register index isn't in `[0..2^P)` range
+    InvalidIndex(usize),
+    /// Estimator isn't in `HyperLogLog` representation
+    NotHyperLogLog,
+}
+
+/// Copy of `HyperLogLog` representation state returned by `CardinalityEstimator::hll_snapshot`
+#[derive(Clone, Debug, PartialEq)]
+pub struct HllSnapshot {
+    /// Precision parameter `P`
+    pub p: usize,
+    /// Width parameter `W`
+    pub w: usize,
+    /// Number of zero registers
+    pub zeros: u32,
+    /// Registers' harmonic sum, i.e. sum of `2^-rank` over all registers
+    pub harmonic_sum: f64,
+    /// Register ranks, one per each of `2^P` registers
+    pub registers: Vec<u32>,
+}
+
+/// Report of merging exact estimators returned by `CardinalityEstimator::merge_with_report`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MergeReport {
+    /// Number of `rhs` encoded hashes added to `self`
+    pub added: usize,
+    /// Number of `rhs` encoded hashes already stored by `self`
+    pub collisions: usize,
+}
+
+/// Error returned by `CardinalityEstimator::merge_capped` when merged estimator would exceed
+/// memory size limit
+#[derive(Debug, PartialEq)]
+pub struct CapExceeded {
+    /// Memory size of merged estimator
+    pub size: usize,
+    /// Memory size limit
+    pub max_bytes: usize,
+}
+
+/// Insertion statistics returned by `CardinalityEstimator::stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InsertStats {
+    /// Total number of inserted items and hashes
+    pub inserts: u64,
+    /// Number of inserts of already stored items detected by exact representations
+    pub duplicates: u64,
+    /// Number of representation upgrades caused by inserts
+    pub upgrades: u64,
+}
+
+impl InsertStats {
+    /// Record insert given representation kind and exact estimate before and after it
+    #[cfg(feature = "stats")]
+    #[inline]
+    fn record(
+        &mut self,
+        before: (RepresentationKind, Option<usize>),
+        after: (RepresentationKind, Option<usize>),
+    ) {
+        self.inserts += 1;
+        self.duplicates += (before.1.is_some() && before == after) as u64;
+        self.upgrades += (before.0 != after.0) as u64;
+    }
+}
+
+/// Sentinel value of `cached_estimate` field denoting that estimate isn't cached
+#[cfg(feature = "cache_estimate")]
+const NO_CACHED_ESTIMATE: usize = usize::MAX;
+
+/// Harmonic sum recompute state set by `CardinalityEstimator::set_recompute_interval`
+#[cfg(feature = "recompute")]
+#[derive(Clone, Copy, Default)]
+struct RecomputeInterval {
+    /// Number of inserts between recomputes, zero disables recomputing
+    interval: u32,
+    /// Number of inserts since last recompute
+    inserts: u32,
+}
+
+#[cfg(feature = "recompute")]
+impl RecomputeInterval {
+    /// Count insert into `HyperLogLog` representation recomputing its harmonic sum
+    /// once interval is reached
+    #[inline]
+    fn on_insert<const P: usize, const W: usize>(&mut self, hll: &mut HyperLogLog<P, W>) {
+        if self.interval == 0 {
+            return;
+        }
+        self.inserts += 1;
+        if self.inserts >= self.interval {
+            self.inserts = 0;
+            hll.recompute_from_histogram();
+        }
+    }
+}
+
+/// Rounding mode used by `CardinalityEstimator::estimate_rounded`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RoundMode {
+    /// Round down
+    Floor,
+    /// Round up
+    Ceil,
+    /// Round half up
+    Nearest,
+}
+
+/// Cardinality estimate report returned by `CardinalityEstimator::estimate_report`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EstimateReport {
+    /// Cardinality estimate
+    pub value: usize,
+    /// Whether estimate is exact (within hash collisions chance)
+    pub is_exact: bool,
+    /// Lower bound of 95% confidence interval
+    pub lower: usize,
+    /// Upper bound of 95% confidence interval
+    pub upper: usize,
+}
+
+/// Diagnostic metrics returned by `CardinalityEstimator::metrics`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SketchMetrics {
+    /// Cardinality estimate
+    pub estimate: usize,
+    /// Representation label: `"small"`, `"array"` or `"hll"`
+    pub representation: &'static str,
+    /// Number of heap allocated bytes, zero for inline `Small` representation
+    pub heap_bytes: usize,
+    /// Relative standard error of estimate, see `estimate_relative_std_error`
+    pub relative_std_error: f64,
+}
+
+/// Extension trait for merging iterators of cardinality estimators
+pub trait MergeExt<T, S, const P: usize, const W: usize>:
+    Iterator<Item = CardinalityEstimator<T, S, P, W>> + Sized
+where
+    T: Hash + ?Sized,
+    S: BuildHasher + Default,
+{
+    /// Fold all cardinality estimators into one using `merge`.
+    /// Returns empty cardinality estimator for empty iterator.
+    #[inline]
+    fn merge_fold(mut self) -> CardinalityEstimator<T, S, P, W> {
+        let mut estimator = self.next().unwrap_or_default();
+        for rhs in self {
+            estimator.merge(&rhs);
+        }
+        estimator
+    }
+}
+
+impl<I, T, S, const P: usize, const W: usize> MergeExt<T, S, P, W> for I
+where
+    I: Iterator<Item = CardinalityEstimator<T, S, P, W>>,
+    T: Hash + ?Sized,
+    S: BuildHasher + Default,
+{
+}
+
+impl<T, S, const P: usize, const W: usize> IntoIterator for CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    type Item = u32;
+    type IntoIter = alloc::vec::IntoIter<u32>;
+
+    /// Consume `CardinalityEstimator` returning iterator over encoded hashes stored by exact
+    /// representations, reusing `Array` representation's allocation.
+    ///
+    /// `HyperLogLog` representation doesn't store individual hashes,
+    /// so iterator is empty in this case.
+    fn into_iter(mut self) -> Self::IntoIter {
+        let hashes = match self.representation() {
+            Representation::Small(small) => small.items().into_iter().filter(|&h| h != 0).collect(),
+            Representation::Array(arr) => {
+                // SAFETY: ownership of array is transferred to returned vector,
+                // so `data` is reset to avoid freeing it on drop
+                let hashes = unsafe { arr.into_vec() };
+                self.data = 0;
+                hashes
+            }
+            Representation::Hll(_) => Vec::new(),
+        };
+        hashes.into_iter()
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> FromIterator<T> for CardinalityEstimator<T, S, P, W>
+where
+    T: Hash,
+    S: BuildHasher + Default,
+{
+    /// Create `CardinalityEstimator` from iterator of hashable items
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut estimator = Self::new();
+        estimator.extend(iter);
+        estimator
+    }
+}
+
+impl<'a, T, S, const P: usize, const W: usize> FromIterator<&'a T>
+    for CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher + Default,
+{
+    /// Create `CardinalityEstimator` from iterator of references to hashable items
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = &'a T>>(iter: I) -> Self {
+        let mut estimator = Self::new();
+        estimator.extend(iter);
+        estimator
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> Extend<T> for CardinalityEstimator<T, S, P, W>
+where
+    T: Hash,
+    S: BuildHasher,
+{
+    /// Insert all hashable items from iterator into `CardinalityEstimator`
+    #[inline]
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(&item);
+        }
+    }
+}
+
+impl<'a, T, S, const P: usize, const W: usize> Extend<&'a T> for CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// Insert all hashable items referenced by iterator into `CardinalityEstimator`
+    #[inline]
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        for item in iter {
+            self.insert(item);
+        }
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> TryFrom<&[u32]> for CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher + Default,
+{
+    type Error = RegisterError;
+
+    /// Create `CardinalityEstimator` from `2^P` register ranks, see `from_registers`
+    #[inline]
+    fn try_from(registers: &[u32]) -> Result<Self, Self::Error> {
+        Self::from_registers(registers)
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> From<u64> for CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher + Default,
+{
+    /// Create `CardinalityEstimator` from precomputed 64-bit hash, see `insert_hash`.
+    ///
+    /// Note that `hash` is treated as a hash rather than hashed as an item of type `T`,
+    /// including `CardinalityEstimator<u64>`, whose `u64` items are counted with `insert`.
+    #[inline]
+    fn from(hash: u64) -> Self {
+        let mut estimator = Self::new();
+        estimator.insert_hash(hash);
+        estimator
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> From<&[u64]> for CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher + Default,
+{
+    /// Create `CardinalityEstimator` from precomputed 64-bit hashes, see `insert_hash`.
+    ///
+    /// Note that slice items are treated as hashes rather than hashed as items of type `T`.
+    #[inline]
+    fn from(hashes: &[u64]) -> Self {
+        Self::from_hashes(hashes.iter().copied())
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> Default for CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> Clone for CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher + Clone,
+{
+    /// Clone `CardinalityEstimator`
+    fn clone(&self) -> Self {
+        let mut estimator = Self::with_hasher(self.build_hasher.clone());
+        estimator.data = self.clone_data();
+        #[cfg(feature = "stats")]
+        {
+            estimator.stats = self.stats;
+        }
+        #[cfg(feature = "recompute")]
+        {
+            estimator.recompute = self.recompute;
+        }
+        estimator
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> Drop for CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// Free memory occupied by `CardinalityEstimator`
+    #[inline]
+    fn drop(&mut self) {
+        unsafe { self.representation().drop() };
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> PartialEq for CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// Compare cardinality estimators.
+    ///
+    /// Equal `data` words are equal without dereferencing, which covers all equal `Small`
+    /// representations, while different representations are told apart by their tags.
+    fn eq(&self, rhs: &Self) -> bool {
+        self.data == rhs.data || self.representation() == rhs.representation()
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> Debug for CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self.representation())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use test_case::test_case;
+
+    #[test_case(0 => "representation: Small(estimate: 0, size: 8), avg_err: 0.0000")]
+    #[test_case(1 => "representation: Small(estimate: 1, size: 8), avg_err: 0.0000")]
+    #[test_case(2 => "representation: Small(estimate: 2, size: 8), avg_err: 0.0000")]
+    #[test_case(3 => "representation: Array(estimate: 3, size: 24), avg_err: 0.0000")]
+    #[test_case(4 => "representation: Array(estimate: 4, size: 24), avg_err: 0.0000")]
+    #[test_case(8 => "representation: Array(estimate: 8, size: 40), avg_err: 0.0000")]
+    #[test_case(16 => "representation: Array(estimate: 16, size: 72), avg_err: 0.0000")]
+    #[test_case(17 => "representation: Array(estimate: 17, size: 136), avg_err: 0.0000")]
+    #[test_case(28 => "representation: Array(estimate: 28, size: 136), avg_err: 0.0000")]
+    #[test_case(29 => "representation: Array(estimate: 29, size: 136), avg_err: 0.0000")]
+    #[test_case(56 => "representation: Array(estimate: 56, size: 264), avg_err: 0.0000")]
     #[test_case(57 => "representation: Array(estimate: 57, size: 264), avg_err: 0.0000")]
     #[test_case(128 => "representation: Array(estimate: 128, size: 520), avg_err: 0.0000")]
     #[test_case(129 => "representation: Hll(estimate: 131, size: 660), avg_err: 0.0001")]
@@ -210,150 +1962,1988 @@ pub mod tests {
     #[test_case(10_000 => "representation: Hll(estimate: 10417, size: 660), avg_err: 0.0281")]
     #[test_case(100_000 => "representation: Hll(estimate: 93099, size: 660), avg_err: 0.0351")]
     fn test_estimator_p10_w5(n: usize) -> String {
-        evaluate_cardinality_estimator(CardinalityEstimator::<usize, WyHash, 10, 5>::new(), n)
+        evaluate_cardinality_estimator(
+            CardinalityEstimator::<usize, BuildHasherDefault<WyHash>, 10, 5>::new(),
+            n,
+        )
+    }
+
+    #[test_case(0 => "representation: Small(estimate: 0, size: 8), avg_err: 0.0000")]
+    #[test_case(1 => "representation: Small(estimate: 1, size: 8), avg_err: 0.0000")]
+    #[test_case(2 => "representation: Small(estimate: 2, size: 8), avg_err: 0.0000")]
+    #[test_case(3 => "representation: Array(estimate: 3, size: 24), avg_err: 0.0000")]
+    #[test_case(4 => "representation: Array(estimate: 4, size: 24), avg_err: 0.0000")]
+    #[test_case(8 => "representation: Array(estimate: 8, size: 40), avg_err: 0.0000")]
+    #[test_case(16 => "representation: Array(estimate: 16, size: 72), avg_err: 0.0000")]
+    #[test_case(32 => "representation: Array(estimate: 32, size: 136), avg_err: 0.0000")]
+    #[test_case(64 => "representation: Array(estimate: 64, size: 264), avg_err: 0.0000")]
+    #[test_case(128 => "representation: Array(estimate: 128, size: 520), avg_err: 0.0000")]
+    #[test_case(129 => "representation: Hll(estimate: 130, size: 3092), avg_err: 0.0001")]
+    #[test_case(256 => "representation: Hll(estimate: 254, size: 3092), avg_err: 0.0029")]
+    #[test_case(512 => "representation: Hll(estimate: 498, size: 3092), avg_err: 0.0068")]
+    #[test_case(1024 => "representation: Hll(estimate: 1012, size: 3092), avg_err: 0.0130")]
+    #[test_case(4096 => "representation: Hll(estimate: 4105, size: 3092), avg_err: 0.0089")]
+    #[test_case(10_000 => "representation: Hll(estimate: 10068, size: 3092), avg_err: 0.0087")]
+    #[test_case(100_000 => "representation: Hll(estimate: 95628, size: 3092), avg_err: 0.0182")]
+    fn test_estimator_p12_w6(n: usize) -> String {
+        evaluate_cardinality_estimator(
+            CardinalityEstimator::<usize, BuildHasherDefault<WyHash>, 12, 6>::new(),
+            n,
+        )
+    }
+
+    #[test_case(0 => "representation: Small(estimate: 0, size: 8), avg_err: 0.0000")]
+    #[test_case(1 => "representation: Small(estimate: 1, size: 8), avg_err: 0.0000")]
+    #[test_case(2 => "representation: Small(estimate: 2, size: 8), avg_err: 0.0000")]
+    #[test_case(3 => "representation: Array(estimate: 3, size: 24), avg_err: 0.0000")]
+    #[test_case(4 => "representation: Array(estimate: 4, size: 24), avg_err: 0.0000")]
+    #[test_case(8 => "representation: Array(estimate: 8, size: 40), avg_err: 0.0000")]
+    #[test_case(16 => "representation: Array(estimate: 16, size: 72), avg_err: 0.0000")]
+    #[test_case(32 => "representation: Array(estimate: 32, size: 136), avg_err: 0.0000")]
+    #[test_case(64 => "representation: Array(estimate: 64, size: 264), avg_err: 0.0000")]
+    #[test_case(128 => "representation: Array(estimate: 128, size: 520), avg_err: 0.0000")]
+    #[test_case(129 => "representation: Hll(estimate: 129, size: 196628), avg_err: 0.0000")]
+    #[test_case(256 => "representation: Hll(estimate: 256, size: 196628), avg_err: 0.0000")]
+    #[test_case(512 => "representation: Hll(estimate: 511, size: 196628), avg_err: 0.0004")]
+    #[test_case(1024 => "representation: Hll(estimate: 1022, size: 196628), avg_err: 0.0014")]
+    #[test_case(4096 => "representation: Hll(estimate: 4100, size: 196628), avg_err: 0.0009")]
+    #[test_case(10_000 => "representation: Hll(estimate: 10007, size: 196628), avg_err: 0.0008")]
+    #[test_case(100_000 => "representation: Hll(estimate: 100240, size: 196628), avg_err: 0.0011")]
+    fn test_estimator_p18_w6(n: usize) -> String {
+        evaluate_cardinality_estimator(
+            CardinalityEstimator::<usize, BuildHasherDefault<WyHash>, 18, 6>::new(),
+            n,
+        )
+    }
+
+    fn evaluate_cardinality_estimator<const P: usize, const W: usize>(
+        mut e: CardinalityEstimator<usize, BuildHasherDefault<WyHash>, P, W>,
+        n: usize,
+    ) -> String {
+        let mut total_relative_error: f64 = 0.0;
+        for i in 0..n {
+            e.insert(&i);
+            let estimate = e.estimate() as f64;
+            let actual = (i + 1) as f64;
+            let error = estimate - actual;
+            let relative_error = error.abs() / actual;
+            total_relative_error += relative_error;
+        }
+
+        let avg_relative_error = total_relative_error / ((n + 1) as f64);
+
+        // Compute the expected standard error for HyperLogLog based on the precision
+        let standard_error = 1.04 / 2.0f64.powi(P as i32).sqrt();
+        let tolerance = 1.2;
+
+        assert!(
+            avg_relative_error <= standard_error * tolerance,
+            "Average relative error {} exceeds acceptable threshold {}",
+            avg_relative_error,
+            standard_error * tolerance
+        );
+
+        format!(
+            "representation: {:?}, avg_err: {:.4}",
+            e, avg_relative_error
+        )
+    }
+
+    #[test_case(0, 0 => "Small(estimate: 0, size: 8)")]
+    #[test_case(0, 1 => "Small(estimate: 1, size: 8)")]
+    #[test_case(1, 0 => "Small(estimate: 1, size: 8)")]
+    #[test_case(1, 1 => "Small(estimate: 2, size: 8)")]
+    #[test_case(1, 2 => "Array(estimate: 3, size: 24)")]
+    #[test_case(2, 1 => "Array(estimate: 3, size: 24)")]
+    #[test_case(2, 2 => "Array(estimate: 4, size: 24)")]
+    #[test_case(2, 3 => "Array(estimate: 5, size: 40)")]
+    #[test_case(2, 4 => "Array(estimate: 6, size: 40)")]
+    #[test_case(4, 2 => "Array(estimate: 6, size: 40)")]
+    #[test_case(3, 2 => "Array(estimate: 5, size: 40)")]
+    #[test_case(3, 3 => "Array(estimate: 6, size: 40)")]
+    #[test_case(3, 4 => "Array(estimate: 7, size: 40)")]
+    #[test_case(4, 3 => "Array(estimate: 7, size: 40)")]
+    #[test_case(4, 4 => "Array(estimate: 8, size: 40)")]
+    #[test_case(4, 8 => "Array(estimate: 12, size: 72)")]
+    #[test_case(8, 4 => "Array(estimate: 12, size: 72)")]
+    #[test_case(4, 12 => "Array(estimate: 16, size: 72)")]
+    #[test_case(12, 4 => "Array(estimate: 16, size: 72)")]
+    #[test_case(1, 127 => "Array(estimate: 128, size: 520)")]
+    #[test_case(1, 128 => "Hll(estimate: 130, size: 3092)")]
+    #[test_case(127, 1 => "Array(estimate: 128, size: 520)")]
+    #[test_case(128, 1 => "Hll(estimate: 130, size: 3092)")]
+    #[test_case(128, 128 => "Hll(estimate: 254, size: 3092)")]
+    #[test_case(512, 512 => "Hll(estimate: 1012, size: 3092)")]
+    #[test_case(10000, 0 => "Hll(estimate: 10068, size: 3092)")]
+    #[test_case(0, 10000 => "Hll(estimate: 10068, size: 3092)")]
+    #[test_case(4, 10000 => "Hll(estimate: 10068, size: 3092)")]
+    #[test_case(10000, 4 => "Hll(estimate: 10068, size: 3092)")]
+    #[test_case(17, 10000 => "Hll(estimate: 10073, size: 3092)")]
+    #[test_case(10000, 17 => "Hll(estimate: 10073, size: 3092)")]
+    #[test_case(10000, 10000 => "Hll(estimate: 19974, size: 3092)")]
+    fn test_merge(lhs_n: usize, rhs_n: usize) -> String {
+        let mut lhs = CardinalityEstimator::<usize, BuildHasherDefault<WyHash>, 12, 6>::new();
+        for i in 0..lhs_n {
+            lhs.insert(&i);
+        }
+
+        let mut rhs = CardinalityEstimator::<usize, BuildHasherDefault<WyHash>, 12, 6>::new();
+        for i in lhs_n..lhs_n + rhs_n {
+            rhs.insert(&i);
+        }
+
+        lhs.merge(&rhs);
+
+        format!("{:?}", lhs)
+    }
+
+    #[test]
+    fn test_insert() {
+        // Create a new CardinalityEstimator.
+        let mut e = CardinalityEstimator::<str, BuildHasherDefault<WyHash>, 12, 6>::new();
+
+        // Ensure initial estimate is 0.
+        assert_eq!(e.estimate(), 0);
+
+        // Insert a test item and validate estimate.
+        e.insert("test item 1");
+        assert_eq!(e.estimate(), 1);
+
+        // Re-insert the same item, estimate should remain the same.
+        e.insert("test item 1");
+        assert_eq!(e.estimate(), 1);
+
+        // Insert a new distinct item, estimate should increase.
+        e.insert("test item 2");
+        assert_eq!(e.estimate(), 2);
+    }
+
+    #[test_case(0; "empty")]
+    #[test_case(1; "small")]
+    #[test_case(10; "array")]
+    #[test_case(1000; "hll")]
+    fn test_merge_empty(n: usize) {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..n {
+            e.insert(&i);
+        }
+        let original = e.clone();
+
+        // Merging empty estimator must leave `self` unchanged.
+        e.merge(&CardinalityEstimator::new());
+        assert_eq!(e, original);
+
+        // Merging into empty estimator must result in a copy of `rhs`.
+        let mut empty = CardinalityEstimator::<usize>::new();
+        empty.merge(&e);
+        assert_eq!(empty, original);
+        assert_eq!(empty.size_of(), original.size_of());
+    }
+
+    #[test]
+    fn test_hll_slice_len() {
+        assert_eq!(
+            CardinalityEstimator::<usize>::hll_slice_len(),
+            4096 * 6 / 32 + 3
+        );
+        assert_eq!(
+            CardinalityEstimator::<usize, BuildHasherDefault<WyHash>, 4, 5>::hll_slice_len(),
+            6
+        );
+        let e: CardinalityEstimator<usize> = (0..10_000).collect();
+        assert_eq!(
+            e.size_of(),
+            size_of::<usize>() + CardinalityEstimator::<usize>::hll_slice_len() * 4
+        );
+    }
+
+    #[test]
+    fn test_sparse_index_bits() {
+        // hashes differing only in bit 26 and sharing rank bits above `P`
+        let hashes = [0x8000_0000_0000_0123, 0x8000_0000_0400_0123];
+
+        type Narrow = CardinalityEstimator<usize, BuildHasherDefault<WyHash>, 12, 6>;
+        type Wide = CardinalityEstimator<usize, BuildHasherDefault<WyHash>, 12, 4>;
+        assert_eq!(Narrow::sparse_index_bits(), 25);
+        assert_eq!(Wide::sparse_index_bits(), 27);
+        let (mut narrow, mut wide) = (Narrow::new(), Wide::new());
+        for hash in hashes {
+            narrow.insert_hash(hash);
+            wide.insert_hash(hash);
+        }
+        assert_eq!(narrow.estimate(), 1);
+        assert_eq!(wide.estimate(), 2);
+    }
+
+    #[test]
+    fn test_new_const() {
+        static EMPTY: CardinalityEstimator<usize> = CardinalityEstimator::new_const();
+
+        assert!(EMPTY.is_empty());
+        assert_eq!(EMPTY, CardinalityEstimator::new());
+        let mut e = EMPTY.clone();
+        e.insert(&1);
+        assert_eq!(e.estimate(), 1);
+        assert_eq!(EMPTY.estimate(), 0);
+
+        let mut e: CardinalityEstimator<str, BuildHasherDefault<WyHash>, 10, 5> =
+            const { CardinalityEstimator::new_const() };
+        e.insert("a");
+        assert_eq!(e.estimate(), 1);
+    }
+
+    #[test]
+    fn test_small_capacity() {
+        type E = CardinalityEstimator<usize>;
+        assert_eq!(E::small_capacity(), 2);
+
+        let mut e = E::new();
+        for i in 0..E::small_capacity() {
+            e.insert(&i);
+            assert!(matches!(e.representation(), Representation::Small(_)));
+            assert_eq!(e.size_of(), 8);
+        }
+
+        e.insert(&E::small_capacity());
+        assert!(matches!(e.representation(), Representation::Array(_)));
+        assert_eq!(e.size_of(), 24);
+    }
+
+    #[test_case(2 => (RepresentationKind::Small, 8); "small full")]
+    #[test_case(3 => (RepresentationKind::Array, 8 + 4 * 4); "array capacity 4")]
+    #[test_case(4 => (RepresentationKind::Array, 8 + 4 * 4); "array capacity 4 full")]
+    #[test_case(5 => (RepresentationKind::Array, 8 + 8 * 4); "array capacity 8")]
+    #[test_case(8 => (RepresentationKind::Array, 8 + 8 * 4); "array capacity 8 full")]
+    #[test_case(9 => (RepresentationKind::Array, 8 + 16 * 4); "array capacity 16")]
+    #[test_case(17 => (RepresentationKind::Array, 8 + 32 * 4); "array capacity 32")]
+    #[test_case(33 => (RepresentationKind::Array, 8 + 64 * 4); "array capacity 64")]
+    #[test_case(65 => (RepresentationKind::Array, 8 + 128 * 4); "array capacity 128")]
+    #[test_case(128 => (RepresentationKind::Array, 8 + 128 * 4); "array full")]
+    #[test_case(129 => (RepresentationKind::Hll, 8 + 771 * 4); "hyperloglog")]
+    fn test_representation_sizes(n: usize) -> (RepresentationKind, usize) {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..n {
+            e.insert(&i);
+        }
+        (e.representation_kind(), e.size_of())
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let build = |range: core::ops::Range<usize>| {
+            let mut e = CardinalityEstimator::<usize>::new();
+            for i in range {
+                e.insert(&i);
+            }
+            e
+        };
+
+        // identical
+        assert!(build(0..0).approx_eq(&build(0..0), 0.0));
+        assert!(build(0..10).approx_eq(&build(0..10), 0.0));
+        assert!(build(0..10_000).approx_eq(&build(0..10_000), 0.0));
+
+        // near-identical
+        assert!(!build(0..100).approx_eq(&build(0..101), 0.0));
+        assert!(build(0..100).approx_eq(&build(0..101), 0.05));
+        assert!(build(0..10_000).approx_eq(&build(0..10_100), 0.05));
+
+        // disjoint
+        assert!(!build(0..10).approx_eq(&build(0..0), 0.5));
+        assert!(!build(0..10).approx_eq(&build(10..20), 0.5));
+        assert!(!build(0..10_000).approx_eq(&build(10_000..20_000), 0.5));
+    }
+
+    #[test_case(0; "no estimators")]
+    #[test_case(1; "single estimator")]
+    #[test_case(5; "few estimators")]
+    #[test_case(50; "many estimators")]
+    fn test_merge_fold(n: usize) {
+        let estimators: Vec<_> = (0..n)
+            .map(|i| {
+                let mut e = CardinalityEstimator::<usize>::new();
+                for j in 0..i * 10 {
+                    e.insert(&j);
+                }
+                e
+            })
+            .collect();
+
+        let merged = CardinalityEstimator::merge_all(&estimators);
+        assert_eq!(estimators.into_iter().merge_fold(), merged);
+    }
+
+    #[test]
+    fn test_insert_and_estimate() {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..10_000 {
+            let estimate = e.insert_and_estimate(&i);
+            assert_eq!(estimate, e.estimate());
+        }
+    }
+
+    #[test]
+    fn test_recompute_accounting() {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..100_000 {
+            e.insert(&i);
+        }
+        let estimate = e.estimate();
+        e.recompute_accounting();
+        assert!(e.estimate().abs_diff(estimate) <= 1);
+    }
+
+    #[test]
+    fn test_insert_prehashed() {
+        let mut e = CardinalityEstimator::<usize>::new();
+        let mut seed = 42;
+        for _ in 0..10_000 {
+            e.insert_prehashed(wyhash::wyrng(&mut seed));
+        }
+        let err = (e.estimate() as f64 - 10_000.0).abs() / 10_000.0;
+        assert!(
+            err < 3.0 * 1.04 / 64.0,
+            "relative error {} is too large",
+            err
+        );
+    }
+
+    #[test_case(0 => "Small(estimate: 0, size: 8)")]
+    #[test_case(2 => "Small(estimate: 2, size: 8)")]
+    #[test_case(100 => "Array(estimate: 100, size: 520)")]
+    #[test_case(1000 => "Hll(estimate: 987, size: 3092)")]
+    fn test_from_iter(n: usize) -> String {
+        let collected: CardinalityEstimator<usize> = (0..n).collect();
+
+        let mut inserted = CardinalityEstimator::<usize>::new();
+        for i in 0..n {
+            inserted.insert(&i);
+        }
+        assert_eq!(collected.estimate(), inserted.estimate());
+
+        let items: Vec<usize> = (0..n).collect();
+        let mut extended = CardinalityEstimator::<usize>::from_iter(&items[..n / 2]);
+        extended.extend(&items[n / 2..]);
+        assert_eq!(extended.estimate(), inserted.estimate());
+
+        format!("{:?}", collected)
+    }
+
+    #[test_case(1 => "Small(estimate: 1, size: 8)")]
+    #[test_case(3 => "Array(estimate: 3, size: 24)")]
+    #[test_case(100 => "Array(estimate: 100, size: 520)")]
+    fn test_from_iter_duplicates(n: usize) -> String {
+        let items: Vec<usize> = (0..1000).map(|i| i % n).collect();
+        let collected: CardinalityEstimator<usize> = items.iter().collect();
+        assert_eq!(collected.estimate(), n);
+
+        let mut extended = CardinalityEstimator::<usize>::new();
+        extended.extend(items);
+        assert_eq!(extended, collected);
+        format!("{:?}", collected)
+    }
+
+    #[test]
+    fn test_reserve() {
+        let mut e = CardinalityEstimator::<usize>::new();
+        e.insert(&0);
+        e.reserve(MAX_CAPACITY - 1);
+        assert!(matches!(e.representation(), Representation::Small(_)));
+        e.reserve(MAX_CAPACITY);
+        assert!(matches!(e.representation(), Representation::Hll(_)));
+        assert_eq!(e.estimate(), 1);
+    }
+
+    #[test_case(0 => (0, true, 0, 0))]
+    #[test_case(2 => (2, true, 2, 2))]
+    #[test_case(100 => (100, true, 100, 100))]
+    #[test_case(10_000 => (10068, false, 9747, 10389))]
+    fn test_estimate_report(n: usize) -> (usize, bool, usize, usize) {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..n {
+            e.insert(&i);
+        }
+        let report = e.estimate_report();
+        assert_eq!(report.value, e.estimate());
+        assert!(report.lower <= n && n <= report.upper);
+        (report.value, report.is_exact, report.lower, report.upper)
+    }
+
+    #[test]
+    fn test_intersection_estimate_many() {
+        let build = |range: core::ops::Range<usize>| {
+            let mut e = CardinalityEstimator::<usize>::new();
+            for i in range {
+                e.insert(&i);
+            }
+            e
+        };
+        type E = CardinalityEstimator<usize>;
+
+        // exact representations
+        let (a, b, c) = (build(0..60), build(20..80), build(40..100));
+        assert_eq!(E::intersection_estimate_many(&[]), Ok(0));
+        assert_eq!(E::intersection_estimate_many(&[&a]), Ok(60));
+        assert_eq!(E::intersection_estimate_many(&[&a, &b]), Ok(40));
+        assert_eq!(E::intersection_estimate_many(&[&a, &b, &c]), Ok(20));
+
+        // approximate representations
+        let (a, b, c) = (
+            build(0..30_000),
+            build(10_000..40_000),
+            build(20_000..50_000),
+        );
+        let estimate = E::intersection_estimate_many(&[&a, &b, &c]).unwrap();
+        assert!(estimate.abs_diff(10_000) < 1_000, "estimate {}", estimate);
+
+        let many = [&a, &b, &c, &a, &b];
+        assert_eq!(
+            E::intersection_estimate_many(&many),
+            Err(IntersectionError::TooManySketches)
+        );
+    }
+
+    #[test]
+    fn test_verify_invariants() {
+        let mut rng = StdRng::seed_from_u64(12345);
+        for _ in 0..100 {
+            let mut lhs = CardinalityEstimator::<u16>::new();
+            let mut rhs = CardinalityEstimator::<u16>::new();
+            for _ in 0..rng.gen_range(0..1000) {
+                let e = if rng.gen() { &mut lhs } else { &mut rhs };
+                e.insert(&rng.gen_range(0..2000));
+                assert_eq!(e.verify_invariants(), Ok(()));
+            }
+            lhs.merge(&rhs);
+            assert_eq!(lhs.verify_invariants(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_verify_invariants_violation() {
+        let mut e = CardinalityEstimator::<usize>::new();
+        e.data = 5 << 33;
+        assert_eq!(
+            e.verify_invariants(),
+            Err(InvariantError::SmallInvalidOrder)
+        );
+        e.data = (5 << 33) | (5 << 2);
+        assert_eq!(
+            e.verify_invariants(),
+            Err(InvariantError::SmallDuplicateHash(5))
+        );
+        e.data = 0;
+
+        for i in 0..1000 {
+            e.insert(&i);
+        }
+        if let Representation::Hll(hll) = e.representation() {
+            hll.data[0] += 1;
+        }
+        assert!(matches!(
+            e.verify_invariants(),
+            Err(InvariantError::HllZerosMismatch { .. })
+        ));
+    }
+
+    #[test_case(50, 50, 0 => "Array(estimate: 100, size: 520)")]
+    #[test_case(50, 50, 25 => "Array(estimate: 75, size: 520)")]
+    #[test_case(60, 60, 60 => "Array(estimate: 60, size: 264)")]
+    #[test_case(3, 60, 0 => "Array(estimate: 63, size: 264)")]
+    #[test_case(100, 100, 0 => "Hll(estimate: 198, size: 3092)")]
+    #[test_case(100, 100, 72 => "Array(estimate: 128, size: 520)")]
+    fn test_merge_arrays(lhs_n: usize, rhs_n: usize, overlap: usize) -> String {
+        let lhs: CardinalityEstimator<usize> = (0..lhs_n).collect();
+        let rhs: CardinalityEstimator<usize> = (lhs_n - overlap..lhs_n - overlap + rhs_n).collect();
+
+        let mut merged = lhs.clone();
+        merged.merge(&rhs);
+        assert_eq!(merged.verify_invariants(), Ok(()));
+
+        let mut expected = lhs.clone();
+        for i in lhs_n - overlap..lhs_n - overlap + rhs_n {
+            expected.insert(&i);
+        }
+        assert_eq!(merged.estimate(), expected.estimate());
+
+        format!("{:?}", merged)
+    }
+
+    #[test_case(0, 0)]
+    #[test_case(1, 10)]
+    #[test_case(100, 10_000)]
+    #[test_case(10_000, 100)]
+    #[test_case(10_000, 10_000)]
+    fn test_union_with(lhs_n: usize, rhs_n: usize) {
+        let lhs: CardinalityEstimator<usize> = (0..lhs_n).collect();
+        let rhs: CardinalityEstimator<usize> = (lhs_n / 2..lhs_n / 2 + rhs_n).collect();
+
+        let mut merged = lhs.clone();
+        merged.merge(&rhs);
+
+        let estimate = lhs.union_with(&rhs).estimate();
+        assert!(estimate.abs_diff(merged.estimate()) <= 1);
+        assert_eq!(estimate, lhs.union_estimate(&rhs));
+    }
+
+    #[test_case(3 => (3, 3, 3))]
+    #[test_case(200 => (198, 199, 198))]
+    #[test_case(1000 => (986, 987, 987))]
+    fn test_estimate_rounded(n: usize) -> (usize, usize, usize) {
+        let e: CardinalityEstimator<usize> = (0..n).collect();
+        assert_eq!(e.estimate_rounded(RoundMode::Nearest), e.estimate());
+        (
+            e.estimate_rounded(RoundMode::Floor),
+            e.estimate_rounded(RoundMode::Ceil),
+            e.estimate_rounded(RoundMode::Nearest),
+        )
+    }
+
+    #[test_case(0 => 0)]
+    #[test_case(1 => 31)]
+    #[test_case(2 => 62)]
+    #[test_case(3 => 96)]
+    #[test_case(128 => 4096)]
+    #[test_case(129 => 4096 * 6 + 64)]
+    #[test_case(10_000 => 4096 * 6 + 64)]
+    fn test_num_bits_used(n: usize) -> usize {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..n {
+            e.insert(&i);
+        }
+        assert!(e.num_bits_used() <= e.size_of() * 8);
+        e.num_bits_used()
+    }
+
+    #[test_case(0 => (0, 1))]
+    #[test_case(1 => (1, 1))]
+    #[test_case(0x1234_5678 => (0x678, 2))]
+    #[test_case(0x0fff_ffff_ffff_ffff => (0xfff, 49))]
+    #[test_case(0x1000 => (0, 2))]
+    fn test_encode_decode_hash(hash: u64) -> (u32, u32) {
+        type E = CardinalityEstimator<usize>;
+        let h = E::encode_hash(hash);
+        assert_eq!(h >> 31, 0);
+
+        // inserting encoded hash must be equivalent to inserting the hash itself
+        let (mut lhs, mut rhs) = (E::new(), E::new());
+        for i in 0..200 {
+            lhs.insert_hash(hash.wrapping_add(i << 40));
+            rhs.insert_encoded_hash(E::encode_hash(hash.wrapping_add(i << 40)));
+        }
+        assert_eq!(lhs, rhs);
+
+        E::decode_hash(h)
+    }
+
+    #[test_case(0 => (0, 1))]
+    #[test_case(0x0000_0000_0000_07ff => (0x7ff, 1))]
+    #[test_case(0x0000_0000_0000_7fff => (0x7ff, 5))]
+    #[test_case(0x0000_0000_0007_ffff => (0x7ff, 9))]
+    #[test_case(0x0000_0000_3fff_ffff => (0x7ff, 15))]
+    #[test_case(0xffff_ffff_ffff_ffff => (0x7ff, 15))]
+    fn test_encode_decode_hash_w4(hash: u64) -> (u32, u32) {
+        type E = CardinalityEstimator<usize, BuildHasherDefault<WyHash>, 11, 4>;
+        let h = E::encode_hash(hash);
+        assert_eq!(h >> 31, 0);
+        let (idx, rank) = E::decode_hash(h);
+
+        // saturated rank must not leak into register index
+        let mut e = E::from_registers(&[0; 1 << 11]).unwrap();
+        e.insert_hash(hash);
+        assert_eq!(e.hll_snapshot().unwrap().registers[idx as usize], rank);
+        (idx, rank)
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid encoded hash")]
+    fn test_insert_invalid_encoded_hash() {
+        CardinalityEstimator::<usize>::new().insert_encoded_hash(1 << 6);
+    }
+
+    #[test]
+    fn test_try_from_registers() {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..10_000 {
+            e.insert(&i);
+        }
+        let Representation::Hll(hll) = e.representation() else {
+            panic!("estimator must be in HyperLogLog representation");
+        };
+        let mut registers: Vec<u32> = (0..4096).map(|idx| hll.get_register(idx)).collect();
+
+        let restored: CardinalityEstimator<usize> = registers.as_slice().try_into().unwrap();
+        assert_eq!(restored.estimate(), e.estimate());
+        assert!(restored.verify_invariants().is_ok());
+
+        let result: Result<CardinalityEstimator<usize>, _> = registers[1..].try_into();
+        assert_eq!(result.unwrap_err(), RegisterError::InvalidLength(4095));
+
+        registers[7] = 64;
+        let result: Result<CardinalityEstimator<usize>, _> = registers.as_slice().try_into();
+        assert_eq!(
+            result.unwrap_err(),
+            RegisterError::InvalidRank { idx: 7, rank: 64 }
+        );
+    }
+
+    #[test_case(30)]
+    #[test_case(MAX_CAPACITY)]
+    fn test_array_keeps_exact_cardinality(n: usize) {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..n {
+            e.insert(&i);
+            assert_eq!(e.estimate(), i + 1);
+        }
+        assert!(matches!(e.representation(), Representation::Array(_)));
+        e.insert(&n);
+        let is_array = matches!(e.representation(), Representation::Array(_));
+        assert_eq!(is_array, n < MAX_CAPACITY);
+    }
+
+    #[test]
+    fn test_estimate_exact() {
+        let mut e = CardinalityEstimator::<usize>::new();
+        assert_eq!(e.estimate_exact(), Some(0));
+        for i in 0..MAX_CAPACITY {
+            e.insert(&i);
+            assert_eq!(e.estimate_exact(), Some(i + 1));
+        }
+        e.insert(&MAX_CAPACITY);
+        assert_eq!(e.estimate_exact(), None);
+    }
+
+    #[test]
+    fn test_register_density() {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..100 {
+            e.insert(&i);
+        }
+        assert_eq!(e.register_density(), None);
+
+        for i in 100..200 {
+            e.insert(&i);
+        }
+        let low = e.register_density().unwrap();
+        for i in 200..50_000 {
+            e.insert(&i);
+        }
+        let high = e.register_density().unwrap();
+        assert!(low > 0.0 && low < 0.1, "low density: {low}");
+        assert!(high > 0.99 && high <= 1.0, "high density: {high}");
+    }
+
+    #[test_case(0)]
+    #[test_case(100)]
+    #[test_case(10_000)]
+    fn test_insert_byte_records(n: usize) {
+        let records: Vec<String> = (0..n).map(|i| format!("record {}", i)).collect();
+        let mut expected = CardinalityEstimator::<[u8]>::new();
+        for record in &records {
+            expected.insert(record.as_bytes());
+        }
+        let mut e = CardinalityEstimator::<[u8]>::new();
+        e.insert_byte_records(records.iter().map(|r| r.as_bytes()));
+        assert_eq!(e, expected);
+        assert_eq!(e.estimate(), expected.estimate());
+    }
+
+    #[test]
+    fn test_hll_snapshot() {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..100 {
+            e.insert(&i);
+        }
+        assert_eq!(e.hll_snapshot(), None);
+
+        for i in 100..10_000 {
+            e.insert(&i);
+        }
+        let snapshot = e.hll_snapshot().unwrap();
+        assert_eq!((snapshot.p, snapshot.w), (12, 6));
+        assert_eq!(snapshot.registers.len(), 4096);
+
+        let zeros = snapshot.registers.iter().filter(|&&r| r == 0).count() as u32;
+        let sum: f64 = snapshot
+            .registers
+            .iter()
+            .map(|&r| 1.0 / (1u64 << r) as f64)
+            .sum();
+        assert_eq!(zeros, snapshot.zeros);
+        assert!((sum - snapshot.harmonic_sum).abs() < sum * 1e-6);
+
+        let estimate = HyperLogLog::<12, 6>::estimate_from(zeros, sum);
+        assert_eq!((estimate + 0.5) as usize, e.estimate());
+    }
+
+    #[test_case(0)]
+    #[test_case(2)]
+    #[test_case(100)]
+    #[test_case(10_000)]
+    fn test_take(n: usize) {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..n {
+            e.insert(&i);
+        }
+        let expected = e.clone();
+
+        let taken = e.take();
+        assert_eq!(taken, expected);
+        assert!(e.is_empty());
+        assert_eq!(e, CardinalityEstimator::new());
+
+        // estimator left behind must remain fully functional
+        e.insert(&0);
+        assert_eq!(e.estimate(), 1);
+    }
+
+    #[test_case(0, 0)]
+    #[test_case(1, 100)]
+    #[test_case(100, 1)]
+    #[test_case(50, 100)]
+    #[test_case(100, 10_000)]
+    #[test_case(10_000, 100)]
+    #[test_case(10_000, 10_000)]
+    fn test_merged(lhs_n: usize, rhs_n: usize) {
+        let mut lhs = CardinalityEstimator::<usize>::new();
+        let mut rhs = CardinalityEstimator::<usize>::new();
+        for i in 0..lhs_n {
+            lhs.insert(&i);
+        }
+        for i in 0..rhs_n {
+            rhs.insert(&(i + lhs_n / 2));
+        }
+        let (lhs_copy, rhs_copy) = (lhs.clone(), rhs.clone());
+
+        let mut expected = lhs.clone();
+        expected.merge(&rhs);
+        let merged = lhs.merged(&rhs);
+
+        assert_eq!(merged.estimate(), expected.estimate());
+        assert_eq!(
+            merged.hll_snapshot().map(|s| s.registers),
+            expected.hll_snapshot().map(|s| s.registers)
+        );
+        assert!(merged.verify_invariants().is_ok());
+        assert_eq!((lhs, rhs), (lhs_copy, rhs_copy));
+    }
+
+    fn representation_transitions<const P: usize, const W: usize>() -> Vec<(usize, String)> {
+        let mut e = CardinalityEstimator::<usize, BuildHasherDefault<WyHash>, P, W>::new();
+        let mut transitions = vec![];
+        let mut prev = String::new();
+        for i in 0..1000 {
+            e.insert(&i);
+            let name = format!("{:?}", e);
+            let name = name[..name.find('(').unwrap()].to_string();
+            if name != prev {
+                transitions.push((i + 1, name.clone()));
+                prev = name;
+            }
+        }
+        transitions
+    }
+
+    #[test]
+    fn test_representation_thresholds() {
+        type E<const P: usize, const W: usize> =
+            CardinalityEstimator<usize, BuildHasherDefault<WyHash>, P, W>;
+        let expected = |small_max: usize, array_max: usize| {
+            vec![
+                (1, "Small".to_string()),
+                (small_max + 1, "Array".to_string()),
+                (array_max + 1, "Hll".to_string()),
+            ]
+        };
+        assert_eq!(
+            representation_transitions::<4, 4>(),
+            expected(E::<4, 4>::SMALL_MAX, E::<4, 4>::ARRAY_MAX)
+        );
+        assert_eq!(
+            representation_transitions::<10, 5>(),
+            expected(E::<10, 5>::SMALL_MAX, E::<10, 5>::ARRAY_MAX)
+        );
+        assert_eq!(
+            representation_transitions::<12, 6>(),
+            expected(E::<12, 6>::SMALL_MAX, E::<12, 6>::ARRAY_MAX)
+        );
+        assert_eq!((E::<12, 6>::SMALL_MAX, E::<12, 6>::ARRAY_MAX), (2, 128));
+    }
+
+    #[test]
+    fn test_from_hashes() {
+        let mut seed = 42;
+        let hashes: Vec<u64> = (0..500).map(|_| wyhash::wyrng(&mut seed)).collect();
+
+        let mut expected = CardinalityEstimator::<usize>::new();
+        for &hash in &hashes {
+            expected.insert_hash(hash);
+        }
+        let e = CardinalityEstimator::<usize>::from(hashes.as_slice());
+        assert_eq!(e.estimate(), expected.estimate());
+        assert_eq!(e.estimate(), 512);
+
+        let e = CardinalityEstimator::<usize>::from_hashes(hashes.iter().copied());
+        assert_eq!(e, CardinalityEstimator::from(hashes.as_slice()));
+
+        let hashes: Vec<u64> = (0..1000).map(|_| wyhash::wyrng(&mut seed)).collect();
+        let e = CardinalityEstimator::<usize>::from_hashes(hashes.iter().copied());
+        assert_eq!(e.estimate(), 1007);
+
+        // duplicated hashes stay in exact representation
+        let duplicated: Vec<u64> = hashes.iter().copied().cycle().take(3000).collect();
+        let e = CardinalityEstimator::<usize>::from(&duplicated[..2]);
+        assert_eq!(e.representation_kind(), RepresentationKind::Small);
+        let e = CardinalityEstimator::<usize>::from(&duplicated[..2000]);
+        assert_eq!(e, CardinalityEstimator::from(hashes.as_slice()));
+
+        let e = CardinalityEstimator::<usize>::from(hashes[0]);
+        assert_eq!(e.estimate(), 1);
+        assert_eq!(e, CardinalityEstimator::from(&hashes[..1]));
+
+        // `u64` converted into estimator of `u64` items is a hash, not an item
+        let mut item = CardinalityEstimator::<u64>::new();
+        item.insert(&hashes[0]);
+        assert_ne!(CardinalityEstimator::<u64>::from(hashes[0]), item);
+    }
+
+    #[test_case(0 => (0, "small", 0))]
+    #[test_case(2 => (2, "small", 0))]
+    #[test_case(100 => (100, "array", 512))]
+    #[test_case(10_000 => (10068, "hll", 3084))]
+    fn test_metrics(n: usize) -> (usize, &'static str, usize) {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..n {
+            e.insert(&i);
+        }
+        let metrics = e.metrics();
+        assert_eq!(metrics.estimate, e.estimate());
+        assert_eq!(metrics.heap_bytes + size_of::<usize>(), e.size_of());
+        assert_eq!(metrics.relative_std_error, e.estimate_relative_std_error());
+        if metrics.representation == "hll" {
+            assert_eq!(metrics.relative_std_error, 1.04 / 64.0);
+        }
+        (metrics.estimate, metrics.representation, metrics.heap_bytes)
+    }
+
+    #[test_case(0, 10_000 => 10068; "hyperloglog")]
+    #[test_case(20, 0 => 792_868_873_464; "beyond u32")]
+    #[test_case(30, 0 => 12_990_363_622_831_486; "beyond u32 by far")]
+    fn test_estimate_u64(rank: u32, n: usize) -> u64 {
+        let mut e = CardinalityEstimator::<usize>::from_registers(&[rank; 4096]).unwrap();
+        for i in 0..n {
+            e.insert(&i);
+        }
+        let estimate = e.estimate_u64();
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(estimate, e.estimate() as u64);
+        if rank > 0 {
+            assert!(estimate > u32::MAX as u64);
+        }
+        estimate
+    }
+
+    #[test_case(10.0 => 100_680)]
+    #[test_case(1.0 => 10068)]
+    #[test_case(0.5 => 5034)]
+    #[test_case(0.0 => 0)]
+    #[test_case(-1.0 => 0)]
+    #[test_case(f64::NAN => 0)]
+    #[test_case(f64::INFINITY => usize::MAX)]
+    fn test_scaled_estimate(factor: f64) -> usize {
+        let e: CardinalityEstimator<usize> = (0..10_000).collect();
+        e.scaled_estimate(factor)
+    }
+
+    #[test_case(100)]
+    #[test_case(1000)]
+    #[test_case(100_000)]
+    fn test_insert_hashes_sorted(n: usize) {
+        let mut seed = 42;
+        let mut hashes: Vec<u64> = (0..n).map(|_| wyhash::wyrng(&mut seed)).collect();
+        let unsorted = CardinalityEstimator::<usize>::from_hashes(hashes.iter().copied());
+
+        hashes.sort_unstable_by_key(|&hash| hash & ((1 << 12) - 1));
+        let mut e = CardinalityEstimator::<usize>::new();
+        let (head, tail) = hashes.split_at(n / 2);
+        e.insert_hashes_sorted(head);
+        e.insert_hashes_sorted(tail);
+        assert_eq!(e.verify_invariants(), Ok(()));
+        assert_eq!(e.estimate(), unsorted.estimate());
+    }
+
+    #[test]
+    fn test_estimate_relative_std_error() {
+        let mut e = CardinalityEstimator::<usize>::new();
+        assert_eq!(e.estimate_relative_std_error(), 0.0);
+        for i in 0..=MAX_CAPACITY {
+            assert_eq!(e.estimate_relative_std_error(), 0.0);
+            e.insert(&i);
+        }
+        assert_eq!(e.estimate_relative_std_error(), 1.04 / 64.0);
+
+        let mut e = CardinalityEstimator::<usize, BuildHasherDefault<WyHash>, 10, 5>::new();
+        for i in 0..10_000 {
+            e.insert(&i);
+        }
+        assert_eq!(e.estimate_relative_std_error(), 1.04 / 32.0);
+    }
+
+    #[test]
+    fn test_with_hasher() {
+        type E = CardinalityEstimator<usize, ahash::RandomState>;
+        #[cfg(not(any(feature = "stats", feature = "recompute", feature = "cache_estimate")))]
+        assert_eq!(core::mem::size_of::<CardinalityEstimator<usize>>(), 8);
+
+        let build_hasher = ahash::RandomState::with_seeds(1, 2, 3, 4);
+        let mut lhs = E::with_hasher(build_hasher.clone());
+        let mut rhs = E::with_hasher(build_hasher);
+        for i in 0..10_000 {
+            lhs.insert(&i);
+            rhs.insert(&(i + 5_000));
+        }
+        lhs.merge(&rhs);
+        let estimate = lhs.estimate();
+        assert!(
+            estimate.abs_diff(15_000) < 15_000 * 3 / 100,
+            "estimate: {estimate}"
+        );
+
+        // cloned estimator keeps the same hasher, so re-inserting items doesn't change it
+        let mut cloned = lhs.clone();
+        for i in 0..15_000 {
+            cloned.insert(&i);
+        }
+        assert_eq!(cloned, lhs);
+    }
+
+    #[test]
+    fn test_insert_batch_count_new() {
+        let mut e = CardinalityEstimator::<usize>::new();
+        assert_eq!(e.insert_batch_count_new(&[1, 2, 2, 1]), Some(2));
+        assert_eq!(e.insert_batch_count_new(&[1, 2, 3, 4, 5, 3]), Some(3));
+        assert_eq!(e.insert_batch_count_new(&[]), Some(0));
+        assert_eq!(e.insert_batch_count_new(&[5, 4, 3, 2, 1]), Some(0));
+
+        let batch: Vec<usize> = (0..200).collect();
+        assert_eq!(e.insert_batch_count_new(&batch), None);
+        assert_eq!(e.insert_batch_count_new(&[1]), None);
+    }
+
+    #[test_case(0)]
+    #[test_case(2)]
+    #[test_case(100)]
+    #[test_case(10_000)]
+    fn test_reset_to_hyperloglog(n: usize) {
+        let mut e: CardinalityEstimator<usize> = (0..n).collect();
+        e.reset_to_hyperloglog();
+        assert!(matches!(e.representation(), Representation::Hll(_)));
+        assert_eq!(e.estimate(), 0);
+        assert_eq!(e.register_density(), Some(0.0));
+        assert!(e.verify_invariants().is_ok());
+
+        e.insert(&0);
+        assert_eq!(e.estimate(), 1);
+    }
+
+    fn check_empty_hyperloglog<const P: usize>() {
+        let mut e = CardinalityEstimator::<usize, BuildHasherDefault<WyHash>, P, 6>::new();
+        e.reset_to_hyperloglog();
+        assert_eq!(e.estimate(), 0);
+        assert_eq!(e.estimate_rounded(RoundMode::Ceil), 0);
+        assert_eq!(e.estimate_report().upper, 0);
+    }
+
+    #[test]
+    fn test_empty_hyperloglog_estimate() {
+        check_empty_hyperloglog::<4>();
+        check_empty_hyperloglog::<8>();
+        check_empty_hyperloglog::<12>();
+        check_empty_hyperloglog::<16>();
+        check_empty_hyperloglog::<18>();
+    }
+
+    #[test]
+    fn test_dedup_sketches() {
+        let build =
+            |range: core::ops::Range<usize>| -> CardinalityEstimator<usize> { range.collect() };
+        let sketches = vec![
+            build(0..10_000),
+            build(100..10_100),
+            build(50_000..60_000),
+            build(0..10_050),
+        ];
+        let deduped = CardinalityEstimator::dedup_sketches(sketches, 0.9);
+        assert_eq!(deduped.len(), 2);
+        assert!(deduped[0].approx_eq(&build(0..10_100), 0.02));
+        assert_eq!(deduped[1], build(50_000..60_000));
+
+        assert_eq!(
+            CardinalityEstimator::<usize>::dedup_sketches(vec![], 0.9).len(),
+            0
+        );
+    }
+
+    #[test_case(0, 0)]
+    #[test_case(1, 2)]
+    #[test_case(2, 2)]
+    #[test_case(2, 100)]
+    #[test_case(100, 2)]
+    #[test_case(50, 50)]
+    #[test_case(100, 100)]
+    #[test_case(100, 200)]
+    #[test_case(200, 100)]
+    fn test_merged_size_of(lhs_n: usize, rhs_n: usize) {
+        let lhs: CardinalityEstimator<usize> = (0..lhs_n).collect();
+        let rhs: CardinalityEstimator<usize> = (lhs_n / 2..lhs_n / 2 + rhs_n).collect();
+        assert_eq!(lhs.merged_size_of(&rhs), lhs.merged(&rhs).size_of());
+    }
+
+    #[test]
+    fn test_merge_capped() {
+        let mut lhs: CardinalityEstimator<usize> = (0..100).collect();
+        let rhs: CardinalityEstimator<usize> = (100..200).collect();
+        let original = lhs.clone();
+
+        assert_eq!(
+            lhs.merge_capped(&rhs, 1024),
+            Err(CapExceeded {
+                size: 3092,
+                max_bytes: 1024
+            })
+        );
+        assert_eq!(lhs, original);
+
+        let overlapping: CardinalityEstimator<usize> = (50..120).collect();
+        assert_eq!(lhs.merge_capped(&overlapping, 1024), Ok(()));
+        assert_eq!(lhs.estimate(), 120);
+        assert_eq!(lhs.size_of(), 520);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..100 {
+            e.insert(&i);
+            e.insert(&i);
+        }
+        for i in 100..200 {
+            e.insert(&i);
+        }
+        let expected = InsertStats {
+            inserts: 300,
+            duplicates: 100,
+            upgrades: 2,
+        };
+        if cfg!(feature = "stats") {
+            assert_eq!(e.stats(), Some(expected));
+            assert_eq!(e.clone().stats(), Some(expected));
+        } else {
+            assert_eq!(e.stats(), None);
+        }
+    }
+
+    #[test]
+    fn test_checked_new() {
+        let e = CardinalityEstimator::<usize>::checked_new().unwrap();
+        assert_eq!(e, CardinalityEstimator::<usize>::new());
+        assert_eq!(
+            CardinalityEstimator::<usize, BuildHasherDefault<WyHash>, 20, 6>::checked_new(),
+            Err(ParamError::InvalidPrecision(20))
+        );
+        assert_eq!(
+            CardinalityEstimator::<usize, BuildHasherDefault<WyHash>, 3, 6>::checked_new(),
+            Err(ParamError::InvalidPrecision(3))
+        );
+        assert_eq!(
+            CardinalityEstimator::<usize, BuildHasherDefault<WyHash>, 12, 8>::checked_new(),
+            Err(ParamError::InvalidWidth(8))
+        );
+    }
+
+    #[test_case(&[], 3; "empty into three")]
+    #[test_case(&[1], 3; "one into three")]
+    #[test_case(&[1, 2], 3; "two into three")]
+    #[test_case(&[1, 200], 4; "two into four")]
+    #[test_case(&[1, 2], 100; "overlapping two into hundred")]
+    #[test_case(&[1000, 2000], 127; "two into hundred twenty seven")]
+    #[test_case(&[1000, 2000], 128; "two into full array")]
+    fn test_merge_small_with_array(lhs_items: &[usize], rhs_n: usize) {
+        let mut lhs: CardinalityEstimator<usize> = lhs_items.iter().collect();
+        let rhs: CardinalityEstimator<usize> = (0..rhs_n).collect();
+        lhs.merge(&rhs);
+
+        let mut expected: CardinalityEstimator<usize> = lhs_items.iter().collect();
+        for i in 0..rhs_n {
+            expected.insert(&i);
+        }
+        assert_eq!(lhs.to_bytes(), expected.to_bytes());
+        assert_eq!(lhs.size_of(), expected.size_of());
+        assert_eq!(lhs.verify_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_params() {
+        let e = CardinalityEstimator::<usize>::new();
+        assert_eq!((e.p(), e.w()), (12, 6));
+        let e = CardinalityEstimator::<usize, BuildHasherDefault<WyHash>, 8, 5>::new();
+        assert_eq!((e.p(), e.w()), (8, 5));
+    }
+
+    #[test_case(0, 0; "no estimators")]
+    #[test_case(1, 2; "single small estimator")]
+    #[test_case(100, 2; "many small estimators")]
+    #[test_case(1000, 20; "many overlapping array estimators")]
+    #[test_case(1000, 50; "many estimators upgrading to hyperloglog")]
+    #[test_case(10, 1000; "few hyperloglog estimators")]
+    fn test_merge_all_prefiltered(n: usize, len: usize) {
+        let estimators: Vec<CardinalityEstimator<usize>> = (0..n)
+            .map(|i| (i % 7 * len / 4..i % 7 * len / 4 + len).collect())
+            .collect();
+
+        let filtered = CardinalityEstimator::merge_all_prefiltered(&estimators);
+        let unfiltered = CardinalityEstimator::merge_all(&estimators);
+        assert_eq!(filtered.to_bytes(), unfiltered.to_bytes());
+        assert_eq!(filtered.verify_invariants(), Ok(()));
+    }
+
+    #[cfg(feature = "recompute")]
+    #[test_case(1; "every insert")]
+    #[test_case(64; "every 64 inserts")]
+    fn test_recompute_interval(interval: u32) {
+        let mut e = CardinalityEstimator::<usize>::new();
+        e.set_recompute_interval(interval);
+        for i in 0..20_000 {
+            e.insert(&i);
+        }
+        let snapshot = e.hll_snapshot().unwrap();
+        let actual: f64 = snapshot
+            .registers
+            .iter()
+            .map(|&rank| 1.0 / (1u64 << rank) as f64)
+            .sum();
+        if interval == 1 {
+            assert_eq!(snapshot.harmonic_sum, actual as f32 as f64);
+        } else {
+            assert!((snapshot.harmonic_sum - actual).abs() < 1e-3 * actual);
+        }
+        assert_eq!(e.clone().hll_snapshot(), Some(snapshot));
+    }
+
+    #[cfg(feature = "cache_estimate")]
+    #[test]
+    fn test_cached_estimate() {
+        let mut e: CardinalityEstimator<usize> = (0..100).collect();
+        assert_eq!(e.estimate(), 100);
+        // exact estimates aren't cached
+        assert_eq!(e.cached_estimate(), None);
+
+        e.extend(100..10_000);
+        let estimate = e.estimate();
+        assert_eq!(e.cached_estimate(), Some(estimate));
+        assert_eq!(e.estimate(), estimate);
+        assert_eq!(e.estimate(), estimate);
+
+        e.insert(&10_000);
+        assert_eq!(e.cached_estimate(), None);
+        e.estimate();
+        e.merge(&(20_000..30_000).collect());
+        assert_eq!(e.cached_estimate(), None);
+        assert_eq!(e.estimate(), e.representation().estimate());
+    }
+
+    #[test_case(0; "empty")]
+    #[test_case(2; "small representation")]
+    #[test_case(100; "array representation")]
+    #[test_case(1000; "hyperloglog representation")]
+    fn test_into_iter(n: usize) {
+        let e: CardinalityEstimator<usize> = (0..n).collect();
+        let mut expected: Vec<u32> = (0..n)
+            .map(|i| CardinalityEstimator::<usize>::encode_hash(e.hasher().hash_one(i)))
+            .collect();
+        expected.sort_unstable();
+
+        let mut hashes: Vec<u32> = e.into_iter().collect();
+        hashes.sort_unstable();
+        if n <= CardinalityEstimator::<usize>::ARRAY_MAX {
+            assert_eq!(hashes, expected);
+        } else {
+            assert!(hashes.is_empty());
+        }
+    }
+
+    #[test_case(10; "small accumulator")]
+    #[test_case(100; "array accumulator")]
+    #[test_case(10_000; "hyperloglog accumulator")]
+    fn test_merge_many(n: usize) {
+        let others: Vec<CardinalityEstimator<usize>> = [2, 50, 5000]
+            .into_iter()
+            .map(|len| (n / 2..n / 2 + len).collect())
+            .collect();
+
+        let mut expected: CardinalityEstimator<usize> = (0..n).collect();
+        for rhs in &others {
+            expected.merge(rhs);
+        }
+        let mut merged: CardinalityEstimator<usize> = (0..n).collect();
+        merged.merge_many(&others);
+        assert_eq!(merged, expected);
+    }
+
+    #[test_case(&[], &[1, 2] => "Small(estimate: 2, size: 8)"; "empty and small")]
+    #[test_case(&[1], &[2] => "Small(estimate: 2, size: 8)"; "disjoint smalls")]
+    #[test_case(&[1, 2], &[2, 1] => "Small(estimate: 2, size: 8)"; "equal smalls")]
+    #[test_case(&[1, 2], &[2, 3] => "Array(estimate: 3, size: 24)"; "overlapping smalls")]
+    #[test_case(&[1, 2, 3, 4], &[3, 4, 5] => "Array(estimate: 5, size: 40)"; "arrays")]
+    fn test_merge_smallest_representation(lhs: &[usize], rhs: &[usize]) -> String {
+        let mut lhs: CardinalityEstimator<usize> = lhs.iter().collect();
+        lhs.merge(&rhs.iter().collect());
+        assert_eq!(lhs.verify_invariants(), Ok(()));
+        format!("{:?}", lhs)
+    }
+
+    #[test]
+    fn test_insert_floats() {
+        let mut e = CardinalityEstimator::<u64>::new();
+        e.insert_f64(0.0);
+        e.insert_f64(-0.0);
+        e.insert_f32(-0.0);
+        assert_eq!(e.estimate(), 1);
+
+        e.insert_f64(f64::NAN);
+        e.insert_f64(-f64::NAN);
+        e.insert_f64(f64::from_bits(f64::NAN.to_bits() | 1));
+        e.insert_f32(f32::NAN);
+        assert_eq!(e.estimate(), 2);
+
+        e.insert_f64(1.5);
+        e.insert_f32(1.5);
+        e.insert_f64(f64::INFINITY);
+        e.insert_f64(f64::NEG_INFINITY);
+        assert_eq!(e.estimate(), 5);
+    }
+
+    #[test_case(0; "empty")]
+    #[test_case(2; "small representation")]
+    #[test_case(100; "array representation")]
+    #[test_case(10_000; "hyperloglog representation")]
+    fn test_merge_rewidth(n: usize) {
+        type E<const W: usize> = CardinalityEstimator<usize, BuildHasherDefault<WyHash>, 12, W>;
+        let lhs_items = || (0..n / 2).chain(n..n + 3);
+        let rhs_items = || 0..n;
+
+        // stored encoded hashes of narrower registers are re-encoded losslessly
+        let mut lhs: E<6> = lhs_items().collect();
+        lhs.merge_rewidth(&rhs_items().collect::<E<5>>());
+        let expected: E<6> = lhs_items().chain(rhs_items()).collect();
+        assert_eq!(lhs.to_bytes(), expected.to_bytes());
+
+        // wider registers are merged as saturated registers
+        let mut lhs: E<4> = lhs_items().collect();
+        lhs.merge_rewidth(&rhs_items().collect::<E<5>>());
+        let expected: E<4> = lhs_items().chain(rhs_items()).collect();
+        assert!(matches!(lhs.representation(), Representation::Hll(_)) || n == 0);
+        assert!(lhs.estimate().abs_diff(expected.estimate()) <= expected.estimate() / 100);
+        assert_eq!(lhs.verify_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn test_register_entropy() {
+        let entropy = |e: &CardinalityEstimator<usize>| e.register_entropy().unwrap();
+        assert_eq!(
+            (0..100)
+                .collect::<CardinalityEstimator<usize>>()
+                .register_entropy(),
+            None
+        );
+
+        let mut empty = CardinalityEstimator::<usize>::new();
+        empty.reset_to_hyperloglog();
+        assert_eq!(entropy(&empty), 0.0);
+
+        // skewed stream of few distinct items leaves most registers zero
+        let mut skewed = CardinalityEstimator::<usize>::new();
+        for i in 0..100_000 {
+            skewed.insert(&(i % 200));
+        }
+        let uniform: CardinalityEstimator<usize> = (0..100_000).collect();
+        assert!(entropy(&skewed) < 0.5);
+        assert!(entropy(&uniform) > 2.0);
+        assert!(entropy(&uniform) <= 6.0);
+    }
+
+    #[test_case(0 => 0)]
+    #[test_case(100 => 100)]
+    #[test_case(10_000 => 10068)]
+    fn test_insert_u64(n: u64) -> usize {
+        let mut e = CardinalityEstimator::<str>::new();
+        let mut expected = CardinalityEstimator::<u64>::new();
+        for x in 0..n {
+            e.insert_u64(x);
+            expected.insert(&x);
+        }
+        assert_eq!(e.to_bytes(), expected.to_bytes());
+        e.estimate()
+    }
+
+    /// Item whose `Hash` implementation panics for `PANIC_ITEM`
+    struct PanickingItem(usize);
+
+    const PANIC_ITEM: usize = usize::MAX;
+
+    impl Hash for PanickingItem {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            assert_ne!(self.0, PANIC_ITEM, "hashing panicked");
+            self.0.hash(state);
+        }
     }
 
-    #[test_case(0 => "representation: Small(estimate: 0, size: 8), avg_err: 0.0000")]
-    #[test_case(1 => "representation: Small(estimate: 1, size: 8), avg_err: 0.0000")]
-    #[test_case(2 => "representation: Small(estimate: 2, size: 8), avg_err: 0.0000")]
-    #[test_case(3 => "representation: Array(estimate: 3, size: 24), avg_err: 0.0000")]
-    #[test_case(4 => "representation: Array(estimate: 4, size: 24), avg_err: 0.0000")]
-    #[test_case(8 => "representation: Array(estimate: 8, size: 40), avg_err: 0.0000")]
-    #[test_case(16 => "representation: Array(estimate: 16, size: 72), avg_err: 0.0000")]
-    #[test_case(32 => "representation: Array(estimate: 32, size: 136), avg_err: 0.0000")]
-    #[test_case(64 => "representation: Array(estimate: 64, size: 264), avg_err: 0.0000")]
-    #[test_case(128 => "representation: Array(estimate: 128, size: 520), avg_err: 0.0000")]
-    #[test_case(129 => "representation: Hll(estimate: 130, size: 3092), avg_err: 0.0001")]
-    #[test_case(256 => "representation: Hll(estimate: 254, size: 3092), avg_err: 0.0029")]
-    #[test_case(512 => "representation: Hll(estimate: 498, size: 3092), avg_err: 0.0068")]
-    #[test_case(1024 => "representation: Hll(estimate: 1012, size: 3092), avg_err: 0.0130")]
-    #[test_case(4096 => "representation: Hll(estimate: 4105, size: 3092), avg_err: 0.0089")]
-    #[test_case(10_000 => "representation: Hll(estimate: 10068, size: 3092), avg_err: 0.0087")]
-    #[test_case(100_000 => "representation: Hll(estimate: 95628, size: 3092), avg_err: 0.0182")]
-    fn test_estimator_p12_w6(n: usize) -> String {
-        evaluate_cardinality_estimator(CardinalityEstimator::<usize, WyHash, 12, 6>::new(), n)
+    #[test_case(0; "empty")]
+    #[test_case(2; "full small")]
+    #[test_case(4; "full array")]
+    #[test_case(128; "array at max capacity")]
+    #[test_case(1000; "hyperloglog")]
+    fn test_panic_safety(n: usize) {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+
+        let mut e = CardinalityEstimator::<PanickingItem>::new();
+        for i in 0..n {
+            e.insert(&PanickingItem(i));
+        }
+        let bytes = e.to_bytes();
+
+        // panic during hashing leaves estimator unchanged and valid
+        let result = catch_unwind(AssertUnwindSafe(|| e.insert(&PanickingItem(PANIC_ITEM))));
+        assert!(result.is_err());
+        assert_eq!(e.to_bytes(), bytes);
+        assert_eq!(e.verify_invariants(), Ok(()));
+
+        // estimator keeps growing through reallocations after the panic
+        for i in n..n + 200 {
+            e.insert(&PanickingItem(i));
+        }
+        assert_eq!(e.verify_invariants(), Ok(()));
+
+        // partially built estimators are dropped exactly once while unwinding
+        let result = catch_unwind(|| {
+            (0..n)
+                .chain([PANIC_ITEM])
+                .map(PanickingItem)
+                .collect::<CardinalityEstimator<PanickingItem>>()
+        });
+        assert!(result.is_err());
+        let result = catch_unwind(|| {
+            let mut e = CardinalityEstimator::<PanickingItem>::new();
+            let rhs: CardinalityEstimator<PanickingItem> = (0..n).map(PanickingItem).collect();
+            e.merge(&rhs);
+            e.insert(&PanickingItem(PANIC_ITEM));
+        });
+        assert!(result.is_err());
     }
 
-    #[test_case(0 => "representation: Small(estimate: 0, size: 8), avg_err: 0.0000")]
-    #[test_case(1 => "representation: Small(estimate: 1, size: 8), avg_err: 0.0000")]
-    #[test_case(2 => "representation: Small(estimate: 2, size: 8), avg_err: 0.0000")]
-    #[test_case(3 => "representation: Array(estimate: 3, size: 24), avg_err: 0.0000")]
-    #[test_case(4 => "representation: Array(estimate: 4, size: 24), avg_err: 0.0000")]
-    #[test_case(8 => "representation: Array(estimate: 8, size: 40), avg_err: 0.0000")]
-    #[test_case(16 => "representation: Array(estimate: 16, size: 72), avg_err: 0.0000")]
-    #[test_case(32 => "representation: Array(estimate: 32, size: 136), avg_err: 0.0000")]
-    #[test_case(64 => "representation: Array(estimate: 64, size: 264), avg_err: 0.0000")]
-    #[test_case(128 => "representation: Array(estimate: 128, size: 520), avg_err: 0.0000")]
-    #[test_case(129 => "representation: Hll(estimate: 129, size: 196628), avg_err: 0.0000")]
-    #[test_case(256 => "representation: Hll(estimate: 256, size: 196628), avg_err: 0.0000")]
-    #[test_case(512 => "representation: Hll(estimate: 511, size: 196628), avg_err: 0.0004")]
-    #[test_case(1024 => "representation: Hll(estimate: 1022, size: 196628), avg_err: 0.0014")]
-    #[test_case(4096 => "representation: Hll(estimate: 4100, size: 196628), avg_err: 0.0009")]
-    #[test_case(10_000 => "representation: Hll(estimate: 10007, size: 196628), avg_err: 0.0008")]
-    #[test_case(100_000 => "representation: Hll(estimate: 100240, size: 196628), avg_err: 0.0011")]
-    fn test_estimator_p18_w6(n: usize) -> String {
-        evaluate_cardinality_estimator(CardinalityEstimator::<usize, WyHash, 18, 6>::new(), n)
+    #[test_case(0, 0 => 0)]
+    #[test_case(1, 1 => 1)]
+    #[test_case(2, 100 => 100)]
+    #[test_case(100, 20 => 20)]
+    #[test_case(100, 100 => 98)]
+    #[test_case(10_000, 10_000 => 9906)]
+    fn test_merge_returning_delta(lhs_n: usize, rhs_n: usize) -> isize {
+        let mut lhs: CardinalityEstimator<usize> = (0..lhs_n).collect();
+        let rhs: CardinalityEstimator<usize> = (lhs_n..lhs_n + rhs_n).collect();
+        let old_estimate = lhs.estimate();
+        let delta = lhs.merge_returning_delta(&rhs);
+        assert_eq!(old_estimate as isize + delta, lhs.estimate() as isize);
+        assert!(delta.abs_diff(rhs_n as isize) <= 2 + rhs_n / 50);
+        delta
     }
 
-    fn evaluate_cardinality_estimator<const P: usize, const W: usize>(
-        mut e: CardinalityEstimator<usize, WyHash, P, W>,
-        n: usize,
-    ) -> String {
-        let mut total_relative_error: f64 = 0.0;
+    #[test_case(0..2, 0..2 => true; "equal small")]
+    #[test_case(0..2, 1..3 => false; "different small")]
+    #[test_case(0..2, 0..3 => false; "small and array")]
+    #[test_case(0..10, 0..10 => true; "equal array")]
+    #[test_case(0..10, 1..11 => false; "equal length different array")]
+    #[test_case(0..10, 0..11 => false; "different length array")]
+    #[test_case(0..1000, 0..1000 => true; "equal hyperloglog")]
+    #[test_case(0..1000, 0..1001 => false; "different hyperloglog")]
+    fn test_eq(lhs: core::ops::Range<usize>, rhs: core::ops::Range<usize>) -> bool {
+        let mut lhs_e = CardinalityEstimator::<usize>::new();
+        lhs.for_each(|i| lhs_e.insert(&i));
+        let mut rhs_e = CardinalityEstimator::<usize>::new();
+        rhs.for_each(|i| rhs_e.insert(&i));
+        assert!(lhs_e == lhs_e.clone());
+        lhs_e == rhs_e
+    }
+
+    /// Hasher returning the last written integer as is
+    #[derive(Default)]
+    struct IdentityHasher(u64);
+
+    impl Hasher for IdentityHasher {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for &byte in bytes {
+                self.0 = (self.0 << 8) | byte as u64;
+            }
+        }
+
+        fn write_u64(&mut self, i: u64) {
+            self.0 = i;
+        }
+
+        fn write_usize(&mut self, i: usize) {
+            self.0 = i as u64;
+        }
+    }
+
+    #[test_case(10, false => None; "exact representation")]
+    #[test_case(129, false => None; "just upgraded")]
+    #[test_case(10_000, false => None; "uniform hasher")]
+    #[test_case(1_000_000, false => None; "uniform hasher with large cardinality")]
+    #[test_case(10_000, true => matches Some(_); "identity hasher")]
+    #[test_case(1_000_000, true => matches Some(_); "identity hasher with large cardinality")]
+    fn test_hash_quality_warning(n: usize, identity: bool) -> Option<&'static str> {
+        if identity {
+            let mut e = CardinalityEstimator::<usize, BuildHasherDefault<IdentityHasher>>::new();
+            for i in 0..n {
+                e.insert(&i);
+            }
+            e.hash_quality_warning()
+        } else {
+            let mut e = CardinalityEstimator::<usize>::new();
+            for i in 0..n {
+                e.insert(&i);
+            }
+            e.hash_quality_warning()
+        }
+    }
+
+    #[test_case(2; "small")]
+    #[test_case(100; "array")]
+    fn test_merge_exact_into_hll(n: usize) {
+        let rhs: CardinalityEstimator<usize> = (n..n + 10_000).collect();
+        let mut lhs = CardinalityEstimator::<usize>::new();
+        for i in 0..n {
+            lhs.insert(&i);
+        }
+
+        // register-wise merge into upgraded `self`
+        let mut expected = lhs.clone();
+        expected.upgrade_to_hll();
+        expected.merge(&rhs);
+
+        lhs.merge(&rhs);
+        assert_eq!(lhs.to_bytes(), expected.to_bytes());
+        assert_eq!(lhs.size_of(), rhs.size_of());
+    }
+
+    #[test_case(0, 10_000 => true; "empty into hyperloglog")]
+    #[test_case(0, 100 => true; "empty into array")]
+    #[test_case(2, 10_000 => true; "small into hyperloglog")]
+    #[test_case(100, 10_000 => true; "array into hyperloglog")]
+    #[test_case(100, 10 => false; "array into array")]
+    #[test_case(10_000, 10_000 => false; "hyperloglog into hyperloglog")]
+    fn test_merge_owned(lhs_n: usize, rhs_n: usize) -> bool {
+        let mut lhs = CardinalityEstimator::<usize>::new();
+        for i in 0..lhs_n {
+            lhs.insert(&i);
+        }
+        let mut rhs = CardinalityEstimator::<usize>::new();
+        for i in lhs_n / 2..lhs_n / 2 + rhs_n {
+            rhs.insert(&i);
+        }
+        let mut expected = lhs.clone();
+        expected.merge(&rhs);
+
+        let rhs_data = rhs.data;
+        lhs.merge_owned(rhs);
+        assert_eq!(lhs, expected);
+        assert_eq!(lhs.verify_invariants(), Ok(()));
+        // `rhs` allocation was moved into `lhs`
+        lhs.data == rhs_data
+    }
+
+    #[test_case(10 => None; "exact representation")]
+    #[test_case(1_000 => Some(0))]
+    #[test_case(10_000 => Some(2))]
+    #[test_case(100_000 => Some(6))]
+    #[test_case(1_000_000 => Some(9))]
+    fn test_median_register_rank(n: usize) -> Option<u32> {
+        let mut e = CardinalityEstimator::<usize>::new();
         for i in 0..n {
             e.insert(&i);
-            let estimate = e.estimate() as f64;
-            let actual = (i + 1) as f64;
-            let error = estimate - actual;
-            let relative_error = error.abs() / actual;
-            total_relative_error += relative_error;
         }
+        e.median_register_rank()
+    }
 
-        let avg_relative_error = total_relative_error / ((n + 1) as f64);
+    #[cfg(feature = "std")]
+    #[test_case(0 => Some(0))]
+    #[test_case(2 => Some(2))]
+    #[test_case(100 => Some(100))]
+    #[test_case(1000 => None)]
+    fn test_into_hashset(n: usize) -> Option<usize> {
+        let e: CardinalityEstimator<usize> = (0..n).collect();
+        let hashes: Vec<u32> = e.clone().into_iter().collect();
+        let set = e.into_hashset()?;
+        assert!(hashes.iter().all(|h| set.contains(h)));
+        Some(set.len())
+    }
 
-        // Compute the expected standard error for HyperLogLog based on the precision
-        let standard_error = 1.04 / 2.0f64.powi(P as i32).sqrt();
-        let tolerance = 1.2;
+    /// Run `f` returning trace events it emitted, captured by subscriber set as default
+    /// for current thread only.
+    #[cfg(feature = "tracing")]
+    fn capture_events(f: impl FnOnce()) -> Vec<String> {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::{span, Event, Metadata, Subscriber};
 
-        assert!(
-            avg_relative_error <= standard_error * tolerance,
-            "Average relative error {} exceeds acceptable threshold {}",
-            avg_relative_error,
-            standard_error * tolerance
+        /// Subscriber capturing events formatted as space separated fields
+        struct CapturingSubscriber(Arc<Mutex<Vec<String>>>);
+
+        /// Visitor formatting event fields
+        struct FieldsVisitor(Vec<String>);
+
+        impl Visit for FieldsVisitor {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                self.0.push(format!("{}={:?}", field.name(), value));
+            }
+        }
+
+        impl Subscriber for CapturingSubscriber {
+            fn enabled(&self, _: &Metadata) -> bool {
+                true
+            }
+
+            fn new_span(&self, _: &span::Attributes) -> span::Id {
+                span::Id::from_u64(1)
+            }
+
+            fn record(&self, _: &span::Id, _: &span::Record) {}
+
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+            fn event(&self, event: &Event) {
+                let mut visitor = FieldsVisitor(vec![]);
+                event.record(&mut visitor);
+                self.0.lock().unwrap().push(visitor.0.join(" "));
+            }
+
+            fn enter(&self, _: &span::Id) {}
+
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let events = Arc::new(Mutex::new(vec![]));
+        tracing::subscriber::with_default(CapturingSubscriber(events.clone()), f);
+        let events = events.lock().unwrap().clone();
+        events
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_trace_upgrades() {
+        let event = |from: &str, to: &str, size: usize| {
+            format!(
+                "message=cardinality estimator representation changed \
+                 from={from:?} to={to:?} size={size}"
+            )
+        };
+        let small_to_array = event("small", "array", 24);
+        let array_to_hll = event("array", "hll", 3092);
+        let small_to_hll = event("small", "hll", 3092);
+        let build = |n: usize| -> CardinalityEstimator<usize> { (0..n).collect() };
+
+        let mut e = CardinalityEstimator::<usize>::new();
+        let messages = capture_events(|| (0..=MAX_CAPACITY).for_each(|i| e.insert(&i)));
+        assert_eq!(messages, [small_to_array.as_str(), array_to_hll.as_str()]);
+
+        let mut e = build(100);
+        let rhs = build(200);
+        assert_eq!(capture_events(|| e.merge(&rhs)), [array_to_hll.as_str()]);
+        assert!(capture_events(|| e.merge(&rhs)).is_empty());
+
+        let (mut e, rhs) = (build(2), build(1000));
+        assert_eq!(
+            capture_events(|| e.merge_owned(rhs)),
+            [small_to_hll.as_str()]
         );
 
-        format!(
-            "representation: {:?}, avg_err: {:.4}",
-            e, avg_relative_error
-        )
+        let mut e = build(2);
+        assert_eq!(capture_events(|| e.reserve(1000)), [small_to_hll.as_str()]);
+
+        let mut e = build(2);
+        assert_eq!(
+            capture_events(|| e.reset_to_hyperloglog()),
+            [small_to_hll.as_str()]
+        );
+
+        let mut e = build(100);
+        let hashes: Vec<u64> = (0..1000).map(|i| i << 12).collect();
+        assert_eq!(
+            capture_events(|| e.insert_hashes_sorted(&hashes)),
+            [array_to_hll.as_str()]
+        );
+
+        let mut e = build(100);
+        let rhs = (0..1000).collect::<CardinalityEstimator<usize, _, 12, 5>>();
+        assert_eq!(
+            capture_events(|| e.merge_rewidth(&rhs)),
+            [array_to_hll.as_str()]
+        );
+
+        let (lhs, rhs) = (build(100), build(200));
+        let messages = capture_events(|| {
+            CardinalityEstimator::merge_all_prefiltered([&lhs, &rhs]);
+        });
+        assert_eq!(messages, [event("small", "array", 520), array_to_hll]);
     }
 
-    #[test_case(0, 0 => "Small(estimate: 0, size: 8)")]
-    #[test_case(0, 1 => "Small(estimate: 1, size: 8)")]
-    #[test_case(1, 0 => "Small(estimate: 1, size: 8)")]
-    #[test_case(1, 1 => "Small(estimate: 2, size: 8)")]
-    #[test_case(1, 2 => "Array(estimate: 3, size: 24)")]
-    #[test_case(2, 1 => "Array(estimate: 3, size: 24)")]
-    #[test_case(2, 2 => "Array(estimate: 4, size: 24)")]
-    #[test_case(2, 3 => "Array(estimate: 5, size: 40)")]
-    #[test_case(2, 4 => "Array(estimate: 6, size: 40)")]
-    #[test_case(4, 2 => "Array(estimate: 6, size: 40)")]
-    #[test_case(3, 2 => "Array(estimate: 5, size: 40)")]
-    #[test_case(3, 3 => "Array(estimate: 6, size: 40)")]
-    #[test_case(3, 4 => "Array(estimate: 7, size: 40)")]
-    #[test_case(4, 3 => "Array(estimate: 7, size: 40)")]
-    #[test_case(4, 4 => "Array(estimate: 8, size: 40)")]
-    #[test_case(4, 8 => "Array(estimate: 12, size: 72)")]
-    #[test_case(8, 4 => "Array(estimate: 12, size: 72)")]
-    #[test_case(4, 12 => "Array(estimate: 16, size: 72)")]
-    #[test_case(12, 4 => "Array(estimate: 16, size: 72)")]
-    #[test_case(1, 127 => "Array(estimate: 128, size: 520)")]
-    #[test_case(1, 128 => "Hll(estimate: 130, size: 3092)")]
-    #[test_case(127, 1 => "Array(estimate: 128, size: 520)")]
-    #[test_case(128, 1 => "Hll(estimate: 130, size: 3092)")]
-    #[test_case(128, 128 => "Hll(estimate: 254, size: 3092)")]
-    #[test_case(512, 512 => "Hll(estimate: 1012, size: 3092)")]
-    #[test_case(10000, 0 => "Hll(estimate: 10068, size: 3092)")]
-    #[test_case(0, 10000 => "Hll(estimate: 10068, size: 3092)")]
-    #[test_case(4, 10000 => "Hll(estimate: 10068, size: 3092)")]
-    #[test_case(10000, 4 => "Hll(estimate: 10068, size: 3092)")]
-    #[test_case(17, 10000 => "Hll(estimate: 10073, size: 3092)")]
-    #[test_case(10000, 17 => "Hll(estimate: 10073, size: 3092)")]
-    #[test_case(10000, 10000 => "Hll(estimate: 19974, size: 3092)")]
-    fn test_merge(lhs_n: usize, rhs_n: usize) -> String {
-        let mut lhs = CardinalityEstimator::<usize, WyHash, 12, 6>::new();
+    #[test_case(0..0, 0..2 => Some(MergeReport { added: 2, collisions: 0 }); "into empty")]
+    #[test_case(0..2, 1..3 => Some(MergeReport { added: 1, collisions: 1 }); "small")]
+    #[test_case(0..2, 0..100 => Some(MergeReport { added: 98, collisions: 2 }); "small and array")]
+    #[test_case(0..100, 50..150 => Some(MergeReport { added: 50, collisions: 50 }); "overlapping arrays")]
+    #[test_case(0..100, 0..100 => Some(MergeReport { added: 0, collisions: 100 }); "equal arrays")]
+    #[test_case(0..100, 100..200 => Some(MergeReport { added: 100, collisions: 0 }); "upgrading arrays")]
+    #[test_case(0..100, 0..1000 => None; "hyperloglog")]
+    fn test_merge_with_report(
+        lhs: core::ops::Range<usize>,
+        rhs: core::ops::Range<usize>,
+    ) -> Option<MergeReport> {
+        let mut lhs_e = CardinalityEstimator::<usize>::new();
+        lhs.for_each(|i| lhs_e.insert(&i));
+        let mut rhs_e = CardinalityEstimator::<usize>::new();
+        rhs.for_each(|i| rhs_e.insert(&i));
+        let mut expected = lhs_e.clone();
+        expected.merge(&rhs_e);
+
+        let report = lhs_e.merge_with_report(&rhs_e);
+        assert_eq!(lhs_e, expected);
+        report
+    }
+
+    #[test_case(0, 0 => 0; "empty")]
+    #[test_case(0, 2 => 2; "small into empty")]
+    #[test_case(1000, 100 => 100; "array into hyperloglog")]
+    #[test_case(100, 1000 => 4196; "hyperloglog into array")]
+    #[test_case(0, 1000 => 4096; "hyperloglog into empty")]
+    #[test_case(1000, 10_000 => 4096; "hyperloglog into hyperloglog")]
+    fn test_merge_cost(lhs_n: usize, rhs_n: usize) -> usize {
+        let mut lhs = CardinalityEstimator::<usize>::new();
         for i in 0..lhs_n {
             lhs.insert(&i);
         }
+        let mut rhs = CardinalityEstimator::<usize>::new();
+        for i in 0..rhs_n {
+            rhs.insert(&i);
+        }
+        lhs.merge_cost(&rhs)
+    }
 
-        let mut rhs = CardinalityEstimator::<usize, WyHash, 12, 6>::new();
-        for i in lhs_n..lhs_n + rhs_n {
+    #[test]
+    fn test_register() {
+        let e: CardinalityEstimator<usize> = (0..100).collect();
+        assert_eq!(e.register(0), None);
+
+        let e: CardinalityEstimator<usize> = (0..10_000).collect();
+        let registers = e.hll_snapshot().unwrap().registers;
+        for idx in [0, 1, 42, 2048, 4095] {
+            assert_eq!(e.register(idx), Some(registers[idx as usize]));
+        }
+        assert!((0..4096).all(|idx| e.register(idx) == Some(registers[idx as usize])));
+        assert_eq!(e.register(4096), None);
+        assert_eq!(e.register(u32::MAX), None);
+    }
+
+    #[test]
+    fn test_set_register_checked() {
+        let mut e = CardinalityEstimator::<usize>::new();
+        assert_eq!(
+            e.set_register_checked(0, 1),
+            Err(RegisterError::NotHyperLogLog)
+        );
+
+        let mut registers = vec![0; 4096];
+        let mut e = CardinalityEstimator::<usize>::from_registers(&registers).unwrap();
+        assert_eq!(
+            e.set_register_checked(4096, 1),
+            Err(RegisterError::InvalidIndex(4096))
+        );
+        assert_eq!(
+            e.set_register_checked(7, 64),
+            Err(RegisterError::InvalidRank { idx: 7, rank: 64 })
+        );
+
+        for (idx, rank) in [(0, 1), (1, 5), (7, 63), (4095, 2), (1, 3), (1, 6), (0, 0)] {
+            e.set_register_checked(idx, rank).unwrap();
+            let register = &mut registers[idx as usize];
+            *register = (*register).max(rank);
+            assert_eq!(e.register(idx), Some(*register));
+            assert_eq!(e.verify_invariants(), Ok(()));
+        }
+        let expected = CardinalityEstimator::<usize>::from_registers(&registers).unwrap();
+        assert_eq!(e, expected);
+        assert_eq!(e.estimate(), expected.estimate());
+        assert_eq!(e.hll_snapshot(), expected.hll_snapshot());
+    }
+
+    #[test_case(100 => None; "exact representation")]
+    #[test_case(1000 => Some("rank,count\n0,3219\n1,410\n2,232\n3,109\n4,66\n5,29\n6,15\n7,10\n8,3\n9,1\n10,2\n".into()))]
+    #[test_case(1_000_000 => matches Some(_); "saturated")]
+    fn test_export_histogram_csv(n: usize) -> Option<String> {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..n {
+            e.insert(&i);
+        }
+        let csv = e.export_histogram_csv()?;
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("rank,count"));
+        let rows: Vec<(u32, u32)> = lines
+            .map(|line| {
+                let (rank, count) = line.split_once(',').unwrap();
+                (rank.parse().unwrap(), count.parse().unwrap())
+            })
+            .collect();
+        assert!(rows.len() <= 1 << 6);
+        assert_eq!(rows.iter().map(|&(_, count)| count).sum::<u32>(), 4096);
+        let registers = e.hll_snapshot().unwrap().registers;
+        for (rank, count) in rows {
+            assert_eq!(
+                registers.iter().filter(|&&r| r == rank).count(),
+                count as usize
+            );
+        }
+        Some(csv)
+    }
+
+    /// Hasher wrapping `WyHash`, which produces the same hashes under a different type
+    #[derive(Default)]
+    struct WrappedWyHash(WyHash);
+
+    impl Hasher for WrappedWyHash {
+        fn finish(&self) -> u64 {
+            self.0.finish()
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            self.0.write(bytes);
+        }
+    }
+
+    #[test_case(0, 2; "small")]
+    #[test_case(10, 100; "array")]
+    #[test_case(100, 1000; "hyperloglog")]
+    fn test_merge_raw(lhs_n: usize, rhs_n: usize) {
+        let mut lhs = CardinalityEstimator::<usize>::new();
+        for i in 0..lhs_n {
+            lhs.insert(&i);
+        }
+        let mut rhs = CardinalityEstimator::<usize, BuildHasherDefault<WrappedWyHash>>::new();
+        let mut expected = lhs.clone();
+        for i in lhs_n / 2..lhs_n / 2 + rhs_n {
             rhs.insert(&i);
+            expected.insert(&i);
         }
 
-        lhs.merge(&rhs);
+        unsafe { lhs.merge_raw(rhs.as_raw()) };
+        assert_eq!(lhs.estimate(), expected.estimate());
+        assert_eq!(lhs.to_unpacked(), expected.to_unpacked());
 
-        format!("{:?}", lhs)
+        // `rhs` is left intact
+        assert_eq!(rhs.verify_invariants(), Ok(()));
+        assert_eq!(rhs.to_bytes(), {
+            let mut e = CardinalityEstimator::<usize>::new();
+            for i in lhs_n / 2..lhs_n / 2 + rhs_n {
+                e.insert(&i);
+            }
+            e.to_bytes()
+        });
+    }
+
+    #[test_case(10, 4)]
+    #[test_case(10, 8)]
+    #[test_case(10, 12)]
+    #[test_case(50, 12)]
+    #[test_case(99, 12)]
+    fn test_forced_hyperloglog_small_estimate(n: usize, p: usize) {
+        fn estimate<const P: usize>(n: usize) -> usize {
+            let mut e = CardinalityEstimator::<usize, BuildHasherDefault<WyHash>, P, 6>::new();
+            e.reset_to_hyperloglog();
+            for i in 0..n {
+                e.insert(&i);
+            }
+            e.estimate()
+        }
+        let estimate = match p {
+            4 => estimate::<4>(n),
+            8 => estimate::<8>(n),
+            _ => estimate::<12>(n),
+        };
+        assert!(estimate.abs_diff(n) <= 2, "{estimate}");
+    }
+
+    #[test_case(0 => (RepresentationKind::Small, "small"))]
+    #[test_case(2 => (RepresentationKind::Small, "small"))]
+    #[test_case(100 => (RepresentationKind::Array, "array"))]
+    #[test_case(1000 => (RepresentationKind::Hll, "hll"))]
+    fn test_representation_kind(n: usize) -> (RepresentationKind, &'static str) {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..n {
+            e.insert(&i);
+        }
+        let kind = e.representation_kind();
+        assert_eq!(kind as usize, e.data & 3);
+        assert_eq!(RepresentationKind::from_u8(kind as u8), Some(kind));
+        (kind, kind.as_str())
     }
 
     #[test]
-    fn test_insert() {
-        // Create a new CardinalityEstimator.
-        let mut e = CardinalityEstimator::<str, WyHash, 12, 6>::new();
+    fn test_representation_kind_from_u8() {
+        let kinds: Vec<RepresentationKind> = (0..=u8::MAX)
+            .filter_map(RepresentationKind::from_u8)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                RepresentationKind::Small,
+                RepresentationKind::Array,
+                RepresentationKind::Hll
+            ]
+        );
+        for kind in kinds {
+            assert_eq!(RepresentationKind::from_u8(kind as u8), Some(kind));
+        }
+        assert_eq!(RepresentationKind::from_u8(2), None);
+    }
 
-        // Ensure initial estimate is 0.
-        assert_eq!(e.estimate(), 0);
+    #[test_case(0 => 0)]
+    #[test_case(1 => 1)]
+    #[test_case(100 => 100)]
+    #[test_case(10_000 => 10068)]
+    fn test_new_dense(n: usize) -> usize {
+        let mut e = CardinalityEstimator::<usize>::new_dense();
+        assert_eq!(e.representation_kind(), RepresentationKind::Hll);
+        for i in 0..n {
+            e.insert(&i);
+            assert_eq!(e.representation_kind(), RepresentationKind::Hll);
+        }
+        let mut expected: CardinalityEstimator<usize> = (0..n).collect();
+        expected.upgrade_to_hll();
+        assert_eq!(e, expected);
+        e.estimate()
+    }
 
-        // Insert a test item and validate estimate.
-        e.insert("test item 1");
-        assert_eq!(e.estimate(), 1);
+    #[test_case(0 => None)]
+    #[test_case(2 => None)]
+    #[test_case(3 => Some(4))]
+    #[test_case(100 => Some(128))]
+    #[test_case(10_000 => Some(HyperLogLog::<12, 6>::HLL_SLICE_LEN))]
+    fn test_merge_take_buffer(n: usize) -> Option<usize> {
+        let mut lhs: CardinalityEstimator<usize> = (0..n / 2).collect();
+        let rhs: CardinalityEstimator<usize> = (n / 4..n).collect();
+        let mut expected = lhs.clone();
+        expected.merge(&rhs);
 
-        // Re-insert the same item, estimate should remain the same.
-        e.insert("test item 1");
-        assert_eq!(e.estimate(), 1);
+        let buffer = lhs.merge_take_buffer(rhs);
+        assert_eq!(lhs, expected);
+        buffer.map(|buffer| {
+            assert!(buffer.is_empty());
+            buffer.capacity()
+        })
+    }
 
-        // Insert a new distinct item, estimate should increase.
-        e.insert("test item 2");
+    #[derive(Hash)]
+    enum Event {
+        Start,
+        Stop,
+        Value(u64),
+        Pair(u64, u64),
+    }
+
+    #[test]
+    fn test_distinct_option_values() {
+        let mut e = CardinalityEstimator::<Option<u64>>::new();
+        e.insert(&None);
+        e.insert(&Some(0));
         assert_eq!(e.estimate(), 2);
+        e.insert(&Some(1));
+        e.insert(&None);
+        assert_eq!(e.estimate(), 3);
+
+        let mut e = CardinalityEstimator::<Option<Option<u64>>>::new();
+        for item in [None, Some(None), Some(Some(0)), Some(Some(0))] {
+            e.insert(&item);
+        }
+        assert_eq!(e.estimate(), 3);
+    }
+
+    #[test]
+    fn test_distinct_tuple_values() {
+        let mut e = CardinalityEstimator::<(u64, u64)>::new();
+        for item in [(0, 0), (0, 1), (1, 0), (1, 1), (0, 0)] {
+            e.insert(&item);
+        }
+        assert_eq!(e.estimate(), 4);
+
+        let mut e = CardinalityEstimator::<(Option<u64>, Option<u64>)>::new();
+        for item in [
+            (None, Some(0)),
+            (Some(0), None),
+            (None, None),
+            (Some(0), Some(0)),
+        ] {
+            e.insert(&item);
+        }
+        assert_eq!(e.estimate(), 4);
+    }
+
+    #[test]
+    fn test_distinct_enum_values() {
+        let mut e = CardinalityEstimator::<Event>::new();
+        for item in [
+            Event::Start,
+            Event::Stop,
+            Event::Value(0),
+            Event::Value(1),
+            Event::Pair(0, 0),
+            Event::Pair(0, 1),
+            Event::Pair(1, 0),
+            Event::Start,
+            Event::Value(0),
+        ] {
+            e.insert(&item);
+        }
+        assert_eq!(e.estimate(), 7);
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..1_000_000 {
+            e.insert(&i);
+        }
+        let registers = |e: &CardinalityEstimator<usize>| -> Vec<Option<u32>> {
+            (0..HyperLogLog::<12, 6>::M as u32)
+                .map(|idx| e.register(idx))
+                .collect()
+        };
+        let before = registers(&e);
+        e.compact();
+        assert_eq!(registers(&e), before);
+
+        let Representation::Hll(hll) = e.representation() else {
+            panic!("expected HyperLogLog representation");
+        };
+        let stored = f32::from_bits(hll.data[1]) as f64;
+        let fresh: f64 = (0..HyperLogLog::<12, 6>::M as u32)
+            .map(|idx| 1.0 / (1u64 << hll.get_register(idx)) as f64)
+            .sum();
+        assert!(
+            (stored - fresh).abs() <= fresh * f32::EPSILON as f64,
+            "stored sum {} differs from fresh sum {}",
+            stored,
+            fresh
+        );
     }
 }
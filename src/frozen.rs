@@ -0,0 +1,123 @@
+//! ## Frozen estimator
+//! Immutable form of `CardinalityEstimator` for read-heavy serving, where estimators are built
+//! once and then queried concurrently by many threads, e.g. shared via `Arc`.
+//!
+//! `FrozenEstimator` only exposes read-only queries, so it can't be modified after freezing.
+//! It is `Send + Sync` whenever its item type `T` and build hasher `S` are.
+use core::fmt::{Debug, Formatter};
+use core::hash::{BuildHasher, BuildHasherDefault, Hash};
+
+use wyhash::WyHash;
+
+use crate::estimator::CardinalityEstimator;
+
+/// Immutable cardinality estimator produced by `CardinalityEstimator::freeze`
+pub struct FrozenEstimator<
+    T,
+    S = BuildHasherDefault<WyHash>,
+    const P: usize = 12,
+    const W: usize = 6,
+> where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    estimator: CardinalityEstimator<T, S, P, W>,
+}
+
+impl<T, S, const P: usize, const W: usize> FrozenEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// Return cardinality estimate
+    #[inline]
+    pub fn estimate(&self) -> usize {
+        self.estimator.estimate()
+    }
+
+    /// Return cardinality estimate of union of `self` and `rhs`
+    #[inline]
+    pub fn union_estimate(&self, rhs: &Self) -> usize
+    where
+        S: Clone,
+    {
+        self.estimator.union_estimate(&rhs.estimator)
+    }
+
+    /// Return Jaccard similarity estimate of `self` and `rhs` in [0.0..1.0] range
+    #[inline]
+    pub fn jaccard(&self, rhs: &Self) -> f64
+    where
+        S: Clone,
+    {
+        self.estimator.jaccard(&rhs.estimator)
+    }
+
+    /// Return memory size of `FrozenEstimator`
+    #[inline]
+    pub fn size_of(&self) -> usize {
+        self.estimator.size_of()
+    }
+
+    /// Convert `FrozenEstimator` back into mutable `CardinalityEstimator`
+    #[inline]
+    pub fn into_inner(self) -> CardinalityEstimator<T, S, P, W> {
+        self.estimator
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> Debug for FrozenEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Frozen({:?})", self.estimator)
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// Convert `CardinalityEstimator` into immutable `FrozenEstimator`
+    #[inline]
+    pub fn freeze(self) -> FrozenEstimator<T, S, P, W> {
+        FrozenEstimator { estimator: self }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+    use test_case::test_case;
+
+    #[test_case(2; "small representation")]
+    #[test_case(100; "array representation")]
+    #[test_case(10_000; "hyperloglog representation")]
+    fn test_freeze(n: usize) {
+        let lhs: CardinalityEstimator<usize> = (0..n).collect();
+        let rhs: CardinalityEstimator<usize> = (n / 2..n + n / 2).collect();
+        let (estimate, union_estimate, jaccard) =
+            (lhs.estimate(), lhs.union_estimate(&rhs), lhs.jaccard(&rhs));
+
+        let lhs = Arc::new(lhs.freeze());
+        let rhs = Arc::new(rhs.freeze());
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                let (lhs, rhs) = (Arc::clone(&lhs), Arc::clone(&rhs));
+                scope.spawn(move || {
+                    assert_eq!(lhs.estimate(), estimate);
+                    assert_eq!(lhs.union_estimate(&rhs), union_estimate);
+                    assert_eq!(lhs.jaccard(&rhs), jaccard);
+                });
+            }
+        });
+
+        let lhs = Arc::into_inner(lhs).unwrap().into_inner();
+        assert_eq!(lhs.estimate(), estimate);
+    }
+}
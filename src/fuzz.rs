@@ -0,0 +1,86 @@
+//! # Fuzzing entry points for CardinalityEstimator
+//!
+//! This module provides functions interpreting arbitrary bytes as sequences of estimator
+//! operations and asserting estimator invariants after each of them, so that fuzz targets
+//! (see `fuzz` directory) only need to forward fuzzer input to them.
+use crate::estimator::CardinalityEstimator;
+
+/// Interpret `data` as a sequence of operations over two estimators `lhs` and `rhs`,
+/// asserting representation invariants after each operation.
+///
+/// Each operation starts with an opcode byte:
+/// - `0`           - insert hash built from up to 8 following bytes into `lhs`
+/// - `1`           - insert hash built from up to 8 following bytes into `rhs`
+/// - `2`           - merge `rhs` into `lhs`
+/// - `3`           - replace `lhs` with its `to_bytes`/`from_bytes` round trip
+///
+/// Opcodes are taken modulo number of operations, so any input is a valid sequence.
+pub fn fuzz_insert_sequence(mut data: &[u8]) {
+    let mut lhs = CardinalityEstimator::<u64>::new();
+    let mut rhs = CardinalityEstimator::<u64>::new();
+
+    while let Some((&opcode, rest)) = data.split_first() {
+        data = rest;
+        match opcode % 4 {
+            0 | 1 => {
+                let (bytes, rest) = data.split_at(data.len().min(8));
+                data = rest;
+                let mut hash = [0u8; 8];
+                hash[..bytes.len()].copy_from_slice(bytes);
+                let estimator = if opcode % 4 == 0 { &mut lhs } else { &mut rhs };
+                let before = estimator.estimate();
+                estimator.insert_hash(u64::from_le_bytes(hash));
+                assert!(estimator.estimate() > 0);
+                if let Some(after) = estimator.estimate_exact() {
+                    assert!(after == before || after == before + 1);
+                }
+            }
+            2 => {
+                let estimate = lhs.estimate().max(rhs.estimate());
+                lhs.merge(&rhs);
+                if lhs.estimate_exact().is_some() {
+                    assert!(lhs.estimate() >= estimate);
+                }
+            }
+            _ => {
+                let bytes = lhs.to_bytes();
+                let decoded = CardinalityEstimator::<u64>::from_bytes(&bytes)
+                    .expect("encoded estimator must be decodable");
+                assert_eq!(decoded.to_bytes(), bytes);
+                assert_eq!(decoded.estimate(), lhs.estimate());
+                lhs = decoded;
+            }
+        }
+        assert_eq!(lhs.verify_invariants(), Ok(()));
+        assert_eq!(rhs.verify_invariants(), Ok(()));
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzz_insert_sequence_fixed() {
+        fuzz_insert_sequence(&[]);
+        fuzz_insert_sequence(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 1, 9, 2, 3, 0]);
+        fuzz_insert_sequence(&[
+            0, 0xde, 0xad, 0xbe, 0xef, 1, 0xde, 0xad, 0xbe, 0xef, 0, 0x01, 2, 3, 7, 6, 0xff, 2, 3,
+        ]);
+    }
+
+    #[test]
+    fn test_fuzz_insert_sequence_representations() {
+        // insert enough distinct hashes into both estimators to reach `HyperLogLog` representation
+        let mut data = vec![];
+        for i in 0u64..300 {
+            data.push((i % 2) as u8);
+            data.extend_from_slice(&wyhash::wyhash(&i.to_le_bytes(), 0).to_le_bytes());
+            if i % 50 == 0 {
+                data.extend_from_slice(&[2, 3]);
+            }
+        }
+        data.extend_from_slice(&[2, 3]);
+        fuzz_insert_sequence(&data);
+    }
+}
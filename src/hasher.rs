@@ -0,0 +1,212 @@
+//! # Hashers module for CardinalityEstimator
+//!
+//! This module provides hashers with stable output which can be used as build hasher `S`
+//! parameter of `CardinalityEstimator` when estimates must be reproducible, e.g. pinned in
+//! tests or persisted, regardless of future changes of the default hasher. Seed is either
+//! fixed at compile time (`FixedSeedWyHashBuilder`) or given at runtime (`SeededWyHashBuilder`).
+//!
+//! It also provides `SaltedBuildHasher`, which salts hashes of another build hasher so that
+//! estimators of different tenants can't be correlated.
+use core::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+
+use wyhash::WyHash;
+
+use crate::estimator::CardinalityEstimator;
+
+/// `WyHash` hasher with fixed `SEED`.
+///
+/// Its output for the same seed and the same sequence of writes is guaranteed to stay
+/// unchanged across crate versions.
+#[derive(Clone)]
+pub struct FixedSeedWyHash<const SEED: u64>(WyHash);
+
+impl<const SEED: u64> Default for FixedSeedWyHash<SEED> {
+    /// Create new instance of `FixedSeedWyHash` seeded with `SEED`
+    #[inline]
+    fn default() -> Self {
+        Self(WyHash::with_seed(SEED))
+    }
+}
+
+impl<const SEED: u64> Hasher for FixedSeedWyHash<SEED> {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0.finish()
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.write(bytes)
+    }
+}
+
+/// Build hasher creating `FixedSeedWyHash` hashers with fixed `SEED`
+pub type FixedSeedWyHashBuilder<const SEED: u64> = BuildHasherDefault<FixedSeedWyHash<SEED>>;
+
+/// Build hasher creating `WyHash` hashers with `seed` given at runtime, e.g. read from config.
+///
+/// Its hashes for the same seed and the same sequence of writes are guaranteed to stay
+/// unchanged across crate versions and equal to hashes of `FixedSeedWyHashBuilder` with
+/// the same seed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeededWyHashBuilder {
+    seed: u64,
+}
+
+impl SeededWyHashBuilder {
+    /// Create new instance of `SeededWyHashBuilder` with given `seed`
+    #[inline]
+    pub const fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// Return seed of created hashers
+    #[inline]
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl BuildHasher for SeededWyHashBuilder {
+    type Hasher = WyHash;
+
+    #[inline]
+    fn build_hasher(&self) -> Self::Hasher {
+        WyHash::with_seed(self.seed)
+    }
+}
+
+impl<T, const P: usize, const W: usize> CardinalityEstimator<T, SeededWyHashBuilder, P, W>
+where
+    T: Hash + ?Sized,
+{
+    /// Creates new instance of `CardinalityEstimator` using `WyHash` with given `seed`.
+    ///
+    /// This is the stable hashing entry point: estimates for the same seed and the same
+    /// sequence of inserted items are guaranteed to be reproducible across crate versions.
+    /// Seeds known at compile time can also be used with `FixedSeedWyHashBuilder`.
+    #[inline]
+    pub fn with_fixed_seed(seed: u64) -> Self {
+        Self::with_hasher(SeededWyHashBuilder::new(seed))
+    }
+}
+
+/// Build hasher mixing `salt` into every hash produced by inner build hasher `S`.
+///
+/// Salt is written into each hasher before the item itself, so estimators using different salts
+/// encode the same items into unrelated hashes and their internal state can't be correlated,
+/// e.g. between tenants sharing the same hasher. This is not a formal differential privacy
+/// mechanism: estimators using the same salt remain comparable and mergeable.
+///
+/// It deliberately doesn't implement `Default`, so constructors building estimators with
+/// default build hasher (e.g. `new`, `merge_all` or `new_dense`) can't silently use salt 0.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SaltedBuildHasher<S = BuildHasherDefault<WyHash>> {
+    build_hasher: S,
+    salt: u64,
+}
+
+impl<S: BuildHasher> SaltedBuildHasher<S> {
+    /// Create new instance of `SaltedBuildHasher` mixing `salt` into hashes of `build_hasher`
+    #[inline]
+    pub fn new(build_hasher: S, salt: u64) -> Self {
+        Self { build_hasher, salt }
+    }
+
+    /// Return salt mixed into hashes
+    #[inline]
+    pub fn salt(&self) -> u64 {
+        self.salt
+    }
+}
+
+impl<S: BuildHasher> BuildHasher for SaltedBuildHasher<S> {
+    type Hasher = S::Hasher;
+
+    #[inline]
+    fn build_hasher(&self) -> Self::Hasher {
+        let mut hasher = self.build_hasher.build_hasher();
+        hasher.write_u64(self.salt);
+        hasher
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> CardinalityEstimator<T, SaltedBuildHasher<S>, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher + Default,
+{
+    /// Creates new instance of `CardinalityEstimator` mixing `salt` into hashes of all
+    /// inserted items. Only estimators using the same salt can be merged, while merged
+    /// estimator keeps salted build hasher of `self`.
+    #[inline]
+    pub fn with_salt(salt: u64) -> Self {
+        Self::with_hasher(SaltedBuildHasher::new(S::default(), salt))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test]
+    fn test_fixed_seed_hash() {
+        let build_hasher = FixedSeedWyHashBuilder::<42>::default();
+        assert_eq!(build_hasher.hash_one(12345usize), 17078238872901121084);
+    }
+
+    #[test]
+    fn test_seeded_hash() {
+        let build_hasher = SeededWyHashBuilder::new(42);
+        assert_eq!(build_hasher.seed(), 42);
+        assert_eq!(build_hasher.hash_one(12345usize), 17078238872901121084);
+    }
+
+    #[test]
+    fn test_with_fixed_seed() {
+        let mut fixed = CardinalityEstimator::<usize, FixedSeedWyHashBuilder<42>>::new();
+        let mut seeded = CardinalityEstimator::<usize, SeededWyHashBuilder>::with_fixed_seed(42);
+        let mut other = CardinalityEstimator::<usize, SeededWyHashBuilder>::with_fixed_seed(43);
+        let mut estimates = vec![];
+        for i in 0..10_000 {
+            fixed.insert(&i);
+            seeded.insert(&i);
+            other.insert(&i);
+            if (i + 1) % 1000 == 0 {
+                estimates.push(fixed.estimate());
+            }
+        }
+        assert_eq!(
+            estimates,
+            vec![1004, 1967, 2955, 3969, 5010, 5964, 6879, 7828, 8777, 9833]
+        );
+        assert_eq!(seeded.to_bytes(), fixed.to_bytes());
+        assert_ne!(other.to_bytes(), fixed.to_bytes());
+    }
+
+    #[test_case(2; "small representation")]
+    #[test_case(100; "array representation")]
+    #[test_case(10_000; "hyperloglog representation")]
+    fn test_with_salt(n: usize) {
+        type E = CardinalityEstimator<usize, SaltedBuildHasher>;
+        let build = |salt| -> E {
+            let mut e = E::with_salt(salt);
+            e.extend(0..n);
+            e
+        };
+        let (lhs, rhs) = (build(1), build(2));
+        assert_eq!(lhs.hasher().salt(), 1);
+        assert_ne!(lhs.to_bytes(), rhs.to_bytes());
+        assert_eq!(lhs.to_bytes(), build(1).to_bytes());
+
+        // each salted estimator is self-consistent
+        for e in [lhs, rhs] {
+            assert!(e.estimate().abs_diff(n) <= n / 50);
+            let mut merged = E::with_salt(e.hasher().salt());
+            merged.merge(&e);
+            assert_eq!(merged.estimate(), e.estimate());
+            assert_eq!(merged.hasher(), e.hasher());
+        }
+    }
+}
@@ -13,15 +13,36 @@
 //! - data[1]       - stores harmonic sum of HyperLogLog registers (`f32` transmuted into `u32`).
 //! - data[2..]     - stores register ranks using `W` bits per each register.
 
-use std::fmt::{Debug, Formatter};
-use std::mem::{size_of, size_of_val};
-use std::slice;
+use core::fmt::{Debug, Formatter};
+use core::mem::{size_of, size_of_val};
+use core::slice;
 
-use crate::representation::RepresentationTrait;
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::math;
+use crate::representation::{InvariantError, RepresentationTrait};
 
 /// Mask used for accessing heap allocated data stored at the pointer in `data` field.
 const PTR_MASK: usize = !3;
 
+/// Largest linear counting estimate used instead of bias corrected estimate,
+/// linear counting is also only used below `2.5 * M` where it remains accurate.
+const LINEAR_COUNTING_THRESHOLD: f64 = 100.0;
+
+/// Lookup table of `2^-rank` values for all ranks representable by `u32` register,
+/// used to update registers' harmonic sum without shifts and float divisions.
+const INV_POW2: [f32; 64] = {
+    let mut table = [0.0; 64];
+    let mut rank = 0;
+    while rank < 64 {
+        table[rank] = 1.0 / (1u64 << rank) as f32;
+        rank += 1;
+    }
+    table
+};
+
 #[derive(PartialEq)]
 pub(crate) struct HyperLogLog<'a, const P: usize = 12, const W: usize = 6> {
     pub(crate) data: &'a mut [u32],
@@ -29,17 +50,19 @@ pub(crate) struct HyperLogLog<'a, const P: usize = 12, const W: usize = 6> {
 
 impl<'a, const P: usize, const W: usize> HyperLogLog<'a, P, W> {
     /// Number of HyperLogLog registers
-    const M: usize = 1 << P;
+    pub(crate) const M: usize = 1 << P;
     /// HyperLogLog representation `u32` slice length based on #registers, stored zero registers, harmonic sum, and
     /// one extra element for branchless register updates (see `set_register` for more details).
-    pub(crate) const HLL_SLICE_LEN: usize = Self::M * W / 32 + 3;
+    /// Registers' bits are rounded up to whole `u32` elements, as `M * W` isn't a multiple of 32
+    /// for the smallest `P` with `W = 5`.
+    pub(crate) const HLL_SLICE_LEN: usize = (Self::M * W).div_ceil(32) + 3;
 
     /// Create new instance of `HyperLogLog` representation from items
     #[inline]
     pub(crate) fn new(items: &[u32]) -> Self {
         let mut hll_data = vec![0u32; Self::HLL_SLICE_LEN];
         let data = (PTR_MASK & hll_data.as_mut_ptr() as usize) | 3;
-        std::mem::forget(hll_data);
+        core::mem::forget(hll_data);
         let mut hll = Self::from(data);
 
         hll.data[0] = Self::M as u32;
@@ -54,27 +77,41 @@ impl<'a, const P: usize, const W: usize> HyperLogLog<'a, P, W> {
 
     /// Return normal index and rank from encoded sparse hash
     #[inline]
-    fn decode_hash(h: u32) -> (u32, u32) {
+    pub(crate) fn decode_hash(h: u32) -> (u32, u32) {
         let rank = h & ((1 << W) - 1);
         let idx = (h >> W) & ((1 << P) - 1);
         (idx, rank)
     }
 
-    /// Insert encoded hash into HyperLogLog representation
+    /// Update HyperLogLog `idx` register to `new_rank` if it exceeds current rank
     #[inline]
-    fn update_rank(&mut self, idx: u32, new_rank: u32) {
+    pub(crate) fn update_rank(&mut self, idx: u32, new_rank: u32) {
         let old_rank = self.get_register(idx);
         if new_rank > old_rank {
             self.set_register(idx, old_rank, new_rank);
         }
     }
 
+    /// Validate in debug builds that register `idx` and its `u32_idx..u32_idx + 2` slice
+    /// elements are within bounds, as register accessors skip bounds checks.
+    #[inline]
+    fn debug_assert_register_bounds(idx: u32, u32_idx: usize) {
+        debug_assert!(
+            (idx as usize) < Self::M,
+            "register index {} is out of [0..{}) range",
+            idx,
+            Self::M
+        );
+        debug_assert!(u32_idx + 2 <= Self::HLL_SLICE_LEN);
+    }
+
     /// Get HyperLogLog `idx` register
     #[inline]
-    fn get_register(&self, idx: u32) -> u32 {
+    pub(crate) fn get_register(&self, idx: u32) -> u32 {
         let bit_idx = (idx as usize) * W;
         let u32_idx = (bit_idx / 32) + 2;
         let bit_pos = bit_idx % 32;
+        Self::debug_assert_register_bounds(idx, u32_idx);
         // SAFETY: `self.data` is always guaranteed to have these elements.
         let bits = unsafe { self.data.get_unchecked(u32_idx..u32_idx + 2) };
         let bits_1 = W.min(32 - bit_pos);
@@ -85,12 +122,24 @@ impl<'a, const P: usize, const W: usize> HyperLogLog<'a, P, W> {
         ((bits[0] >> bit_pos) & mask_1) | ((bits[1] & mask_2) << bits_1)
     }
 
-    /// Set HyperLogLog `idx` register to new value `rank`
+    /// Set HyperLogLog `idx` register from `old_rank` to larger `new_rank`.
+    ///
+    /// Registers only grow, so register stops being zero at most once: number of zero registers
+    /// is decremented only when `old_rank` is zero, while harmonic sum always replaces old
+    /// register's `2^-old_rank` term (`1.0` for zero register, as initially seeded) with new one.
     #[inline]
     fn set_register(&mut self, idx: u32, old_rank: u32, new_rank: u32) {
         let bit_idx = (idx as usize) * W;
         let u32_idx = (bit_idx / 32) + 2;
         let bit_pos = bit_idx % 32;
+        Self::debug_assert_register_bounds(idx, u32_idx);
+        debug_assert!(new_rank < (1 << W), "rank {} exceeds {} bits", new_rank, W);
+        debug_assert!(
+            new_rank > old_rank,
+            "rank {} doesn't exceed {}",
+            new_rank,
+            old_rank
+        );
         // SAFETY: `self.data` is always guaranteed to have these elements.
         let bits = unsafe { self.data.get_unchecked_mut(u32_idx..u32_idx + 2) };
         let bits_1 = W.min(32 - bit_pos);
@@ -110,11 +159,91 @@ impl<'a, const P: usize, const W: usize> HyperLogLog<'a, P, W> {
         zeros_and_sum[0] -= (old_rank == 0) as u32 & (zeros_and_sum[0] > 0) as u32;
 
         let mut sum = f32::from_bits(zeros_and_sum[1]);
-        sum -= 1.0 / ((1u64 << (old_rank as u64)) as f32);
-        sum += 1.0 / ((1u64 << (new_rank as u64)) as f32);
+        // SAFETY: ranks are always less than `2^W`, i.e. less than 64.
+        sum -= unsafe { INV_POW2.get_unchecked(old_rank as usize) };
+        sum += unsafe { INV_POW2.get_unchecked(new_rank as usize) };
         zeros_and_sum[1] = sum.to_bits();
     }
 
+    /// Return unrounded cardinality estimate of `HyperLogLog` representation
+    #[inline]
+    pub(crate) fn estimate_f64(&self) -> f64 {
+        // SAFETY: `self.data` is always guaranteed to have 0-th and 1-st elements.
+        let zeros = unsafe { *self.data.get_unchecked(0) };
+        let sum = f32::from_bits(unsafe { *self.data.get_unchecked(1) }) as f64;
+        Self::estimate_from(zeros, sum)
+    }
+
+    /// Compute HyperLogLog++ estimate with LogLog-Beta bias correction
+    /// from number of zero registers and registers' harmonic sum.
+    #[inline]
+    pub(crate) fn estimate_from(zeros: u32, sum: f64) -> f64 {
+        // all registers are zero, so return exact zero regardless of bias correction
+        if zeros as usize == Self::M {
+            return 0.0;
+        }
+        // linear counting is more accurate than bias corrected estimate for tiny cardinalities,
+        // e.g. of estimators forced into `HyperLogLog` representation
+        if zeros > 0 {
+            let m = Self::M as f64;
+            let linear_counting = m * math::ln(m / zeros as f64);
+            if linear_counting <= LINEAR_COUNTING_THRESHOLD.min(2.5 * m) {
+                return linear_counting;
+            }
+        }
+        alpha(Self::M) * ((Self::M * (Self::M - zeros as usize)) as f64)
+            / (sum + beta_horner(zeros as f64, P))
+    }
+
+    /// Return histogram of register ranks, where `i`-th bucket stores number of registers of rank `i`.
+    #[inline]
+    pub(crate) fn histogram(&self) -> [u32; 64] {
+        let mut histogram = [0u32; 64];
+        for idx in 0..Self::M as u32 {
+            histogram[self.get_register(idx) as usize] += 1;
+        }
+        histogram
+    }
+
+    /// Recompute number of zero registers and harmonic sum from registers' histogram.
+    ///
+    /// Registers are scanned once to build histogram, after which harmonic sum is computed
+    /// from at most 64 buckets (`sum += count[r] / 2^r`) rather than per register.
+    #[inline]
+    pub(crate) fn recompute_from_histogram(&mut self) {
+        let [zeros, sum] = self.canonical_accounting();
+        self.data[0] = zeros;
+        self.data[1] = sum;
+    }
+
+    /// Return number of zero registers and registers' harmonic sum (as `f32` bits) computed
+    /// from registers' histogram, which doesn't depend on order of register updates
+    /// unlike incrementally maintained values.
+    #[inline]
+    pub(crate) fn canonical_accounting(&self) -> [u32; 2] {
+        let histogram = self.histogram();
+        let sum: f64 = histogram
+            .iter()
+            .enumerate()
+            .map(|(rank, &count)| count as f64 / (1u64 << rank) as f64)
+            .sum();
+        [histogram[0], (sum as f32).to_bits()]
+    }
+
+    /// Return cardinality estimate of union of two `HyperLogLog` representations
+    /// without merging them, by scanning maximum ranks of both registers.
+    #[inline]
+    pub(crate) fn union_estimate(&self, rhs: &HyperLogLog<P, W>) -> usize {
+        let mut zeros = 0;
+        let mut sum = 0.0f64;
+        for idx in 0..Self::M as u32 {
+            let rank = self.get_register(idx).max(rhs.get_register(idx));
+            zeros += (rank == 0) as u32;
+            sum += 1.0 / (1u64 << rank) as f64;
+        }
+        (Self::estimate_from(zeros, sum) + 0.5) as usize
+    }
+
     /// Merge two `HyperLogLog` representations.
     #[inline]
     pub(crate) fn merge(&mut self, rhs: &HyperLogLog<P, W>) {
@@ -140,12 +269,7 @@ impl<'a, const P: usize, const W: usize> RepresentationTrait for HyperLogLog<'a,
     /// Return cardinality estimate of `HyperLogLog` representation
     #[inline]
     fn estimate(&self) -> usize {
-        // SAFETY: `self.data` is always guaranteed to have 0-th and 1-st elements.
-        let zeros = unsafe { *self.data.get_unchecked(0) };
-        let sum = f32::from_bits(unsafe { *self.data.get_unchecked(1) }) as f64;
-        let estimate = alpha(Self::M) * ((Self::M * (Self::M - zeros as usize)) as f64)
-            / (sum + beta_horner(zeros as f64, P));
-        (estimate + 0.5) as usize
+        (self.estimate_f64() + 0.5) as usize
     }
 
     /// Return memory size of `HyperLogLog`
@@ -166,6 +290,27 @@ impl<'a, const P: usize, const W: usize> RepresentationTrait for HyperLogLog<'a,
     fn to_data(&self) -> usize {
         (PTR_MASK & self.data.as_ptr() as usize) | 3
     }
+
+    /// Verify that stored number of zero registers and harmonic sum match registers.
+    /// Harmonic sum is compared with relative tolerance due to its `f32` incremental updates.
+    fn verify_invariants(&self) -> Result<(), InvariantError> {
+        let histogram = self.histogram();
+        let stored = self.data[0];
+        if stored != histogram[0] {
+            let actual = histogram[0];
+            return Err(InvariantError::HllZerosMismatch { stored, actual });
+        }
+        let actual: f64 = histogram
+            .iter()
+            .enumerate()
+            .map(|(rank, &count)| count as f64 / (1u64 << rank) as f64)
+            .sum();
+        let stored = f32::from_bits(self.data[1]);
+        if (stored as f64 - actual).abs() > actual * 1e-3 {
+            return Err(InvariantError::HllSumMismatch { stored, actual });
+        }
+        Ok(())
+    }
 }
 
 impl<const P: usize, const W: usize> From<usize> for HyperLogLog<'_, P, W> {
@@ -180,11 +325,14 @@ impl<const P: usize, const W: usize> From<usize> for HyperLogLog<'_, P, W> {
 }
 
 impl<'a, const P: usize, const W: usize> From<Vec<u32>> for HyperLogLog<'a, P, W> {
-    /// Create new instance of `HyperLogLog` from given `hll_data`
+    /// Create new instance of `HyperLogLog` from given `hll_data`, e.g. deserialized vector.
+    /// Vector is shrunk to its length if needed, as representation is freed as boxed slice
+    /// of `HLL_SLICE_LEN` elements.
     #[inline]
-    fn from(mut hll_data: Vec<u32>) -> Self {
+    fn from(hll_data: Vec<u32>) -> Self {
+        debug_assert_eq!(hll_data.len(), Self::HLL_SLICE_LEN);
+        let hll_data = Box::leak(hll_data.into_boxed_slice());
         let data = (PTR_MASK & hll_data.as_mut_ptr() as usize) | 3;
-        std::mem::forget(hll_data);
         Self::from(data)
     }
 }
@@ -195,13 +343,13 @@ impl<const P: usize, const W: usize> Clone for HyperLogLog<'_, P, W> {
     fn clone(&self) -> Self {
         let mut hll_data = self.data.to_vec();
         let data = (PTR_MASK & hll_data.as_mut_ptr() as usize) | 3;
-        std::mem::forget(hll_data);
+        core::mem::forget(hll_data);
         Self::from(data)
     }
 }
 
 impl<const P: usize, const W: usize> Debug for HyperLogLog<'_, P, W> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         f.write_str(&self.to_string())
     }
 }
@@ -223,7 +371,7 @@ fn alpha(m: usize) -> f64 {
 #[inline]
 fn beta_horner(z: f64, precision: usize) -> f64 {
     let beta = BETA[precision - 4];
-    let zl = (z + 1.0).ln();
+    let zl = math::ln(z + 1.0);
     let mut res = 0.0;
     for i in (1..8).rev() {
         res = res * zl + beta[i];
@@ -399,3 +547,149 @@ const BETA: [[f64; 8]; 15] = [
         0.00291076804642205,
     ],
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0; "empty")]
+    #[test_case(100; "sparse")]
+    #[test_case(100_000; "dense")]
+    fn test_recompute_from_histogram(n: u32) {
+        let mut hll = HyperLogLog::<12, 6>::new(&[]);
+        for i in 0..n {
+            let h = wyhash::wyhash(&i.to_le_bytes(), 0);
+            hll.insert_encoded_hash((h as u32) >> 1);
+        }
+
+        // naive per-register recomputation
+        let mut zeros = 0;
+        let mut sum = 0.0f64;
+        for idx in 0..HyperLogLog::<12, 6>::M as u32 {
+            let rank = hll.get_register(idx);
+            zeros += (rank == 0) as u32;
+            sum += 1.0 / (1u64 << rank) as f64;
+        }
+
+        hll.recompute_from_histogram();
+        assert_eq!(hll.data[0], zeros);
+        assert_eq!(hll.data[1], (sum as f32).to_bits());
+
+        unsafe { hll.drop() };
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "register index 4096 is out of [0..4096) range")]
+    fn test_get_register_out_of_bounds() {
+        let hll = HyperLogLog::<12, 6>::new(&[]);
+        hll.get_register(4096);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "register index 16 is out of [0..16) range")]
+    fn test_set_register_out_of_bounds() {
+        let mut hll = HyperLogLog::<4, 4>::new(&[]);
+        hll.set_register(16, 0, 1);
+    }
+
+    #[test_case(&[3, 5]; "growing ranks")]
+    #[test_case(&[5, 3]; "shrinking ranks")]
+    #[test_case(&[3, 3, 5, 5]; "repeated ranks")]
+    fn test_set_same_register_twice(ranks: &[u32]) {
+        let mut hll = HyperLogLog::<12, 6>::new(&[]);
+        for &rank in ranks {
+            hll.insert_encoded_hash((7 << 6) | rank);
+        }
+        assert_eq!(hll.get_register(7), 5);
+        assert_eq!(hll.data[0], 4095);
+        assert_eq!(f32::from_bits(hll.data[1]), 4095.0 + 1.0 / 32.0);
+        assert_eq!(hll.verify_invariants(), Ok(()));
+
+        unsafe { hll.drop() };
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "rank 3 doesn't exceed 3")]
+    fn test_set_register_same_rank() {
+        let mut hll = HyperLogLog::<4, 4>::new(&[]);
+        hll.set_register(1, 3, 3);
+    }
+
+    /// Set every register to every rank representable by `W` bits in increasing order,
+    /// verifying that neighbouring registers (including ones straddling `u32` boundaries)
+    /// are unaffected.
+    fn check_all_ranks<const P: usize, const W: usize>() {
+        let mut hll = HyperLogLog::<P, W>::new(&[]);
+        let m = HyperLogLog::<P, W>::M as u32;
+        let max_rank: u32 = (1 << W) - 1;
+        for rank in 1..=max_rank {
+            for idx in 0..m {
+                // registers lag behind each other so that neighbours always hold distinct ranks
+                let expected = rank.saturating_sub(idx % 3);
+                if expected > 0 {
+                    hll.insert_encoded_hash((idx << W) | expected);
+                }
+            }
+            for idx in 0..m {
+                assert_eq!(hll.get_register(idx), rank.saturating_sub(idx % 3));
+            }
+            assert_eq!(hll.verify_invariants(), Ok(()));
+        }
+        // registers of the last slice element must not leak into the extra element
+        assert_eq!(hll.data[HyperLogLog::<P, W>::HLL_SLICE_LEN - 1], 0);
+
+        unsafe { hll.drop() };
+    }
+
+    #[test]
+    fn test_all_ranks_w4() {
+        check_all_ranks::<4, 4>();
+        check_all_ranks::<5, 4>();
+        check_all_ranks::<8, 4>();
+        check_all_ranks::<12, 4>();
+        check_all_ranks::<16, 4>();
+    }
+
+    #[test]
+    fn test_all_ranks_w5_w6() {
+        check_all_ranks::<4, 5>();
+        check_all_ranks::<5, 5>();
+        check_all_ranks::<12, 5>();
+        check_all_ranks::<4, 6>();
+        check_all_ranks::<12, 6>();
+    }
+
+    #[test]
+    fn test_inv_pow2_table() {
+        for (rank, &value) in INV_POW2.iter().enumerate() {
+            assert_eq!(value, 1.0 / ((1u64 << rank) as f32));
+        }
+    }
+
+    #[test_case(100; "sparse")]
+    #[test_case(100_000; "dense")]
+    fn test_inv_pow2_estimate_unchanged(n: u32) {
+        let mut hll = HyperLogLog::<12, 6>::new(&[]);
+        // harmonic sum maintained with shift and float division, as before lookup table
+        let mut sum = f32::from_bits(hll.data[1]);
+        for i in 0..n {
+            let h = (wyhash::wyhash(&i.to_le_bytes(), 0) as u32) >> 1;
+            let (idx, rank) = HyperLogLog::<12, 6>::decode_hash(h);
+            let old_rank = hll.get_register(idx);
+            hll.insert_encoded_hash(h);
+            if rank > old_rank {
+                sum -= 1.0 / ((1u64 << (old_rank as u64)) as f32);
+                sum += 1.0 / ((1u64 << (rank as u64)) as f32);
+            }
+        }
+        assert_eq!(hll.data[1], sum.to_bits());
+        let expected = HyperLogLog::<12, 6>::estimate_from(hll.data[0], sum as f64);
+        assert_eq!(hll.estimate(), (expected + 0.5) as usize);
+
+        unsafe { hll.drop() };
+    }
+}
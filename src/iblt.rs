@@ -0,0 +1,226 @@
+//! ## Invertible Bloom Lookup Table
+//! Allows to reconcile exact estimators across nodes sharing most of their items, without
+//! exchanging all stored encoded hashes: each node sends `Iblt` of its encoded hashes, which is
+//! subtracted from the local one and decoded into symmetric difference of both sets, as long
+//! as the difference is small compared to the number of cells.
+//!
+//! Each encoded hash is added to one cell in each of `NUM_HASHES` equally sized partitions
+//! of the table. Cell stores:
+//! - `count`       - number of added minus number of subtracted encoded hashes
+//! - `key_sum`     - XOR of encoded hashes
+//! - `check_sum`   - XOR of check hashes of encoded hashes, used to detect pure cells
+use core::hash::{BuildHasher, Hash};
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::estimator::CardinalityEstimator;
+use crate::representation::Representation;
+
+/// Number of cells each encoded hash is added to
+const NUM_HASHES: usize = 3;
+
+/// Invertible Bloom Lookup Table of encoded hashes produced by `CardinalityEstimator::to_iblt`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Iblt {
+    cells: Vec<Cell>,
+}
+
+/// Single `Iblt` cell
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Cell {
+    count: i32,
+    key_sum: u32,
+    check_sum: u32,
+}
+
+impl Cell {
+    /// Return true if cell holds exactly one added or subtracted encoded hash
+    #[inline]
+    fn is_pure(&self) -> bool {
+        (self.count == 1 || self.count == -1) && self.check_sum == check_hash(self.key_sum)
+    }
+}
+
+impl Iblt {
+    /// Create empty `Iblt` with `cells` rounded up to a multiple of number of hash functions
+    pub fn new(cells: usize) -> Self {
+        let cells = cells.max(1).div_ceil(NUM_HASHES) * NUM_HASHES;
+        Self {
+            cells: vec![Cell::default(); cells],
+        }
+    }
+
+    /// Return number of cells
+    #[inline]
+    pub fn cells(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Add encoded hash to `Iblt`
+    #[inline]
+    pub fn insert(&mut self, h: u32) {
+        let check_sum = check_hash(h);
+        for i in self.indices(h) {
+            let cell = &mut self.cells[i];
+            cell.count += 1;
+            cell.key_sum ^= h;
+            cell.check_sum ^= check_sum;
+        }
+    }
+
+    /// Subtract `rhs` from `self` returning `Iblt` of their symmetric difference,
+    /// or `None` if tables have different number of cells.
+    pub fn subtract(&self, rhs: &Iblt) -> Option<Iblt> {
+        if self.cells() != rhs.cells() {
+            return None;
+        }
+        let cells = self
+            .cells
+            .iter()
+            .zip(&rhs.cells)
+            .map(|(lhs, rhs)| Cell {
+                count: lhs.count - rhs.count,
+                key_sum: lhs.key_sum ^ rhs.key_sum,
+                check_sum: lhs.check_sum ^ rhs.check_sum,
+            })
+            .collect();
+        Some(Iblt { cells })
+    }
+
+    /// Decode difference produced by `subtract` into encoded hashes only present in the
+    /// left-hand side and encoded hashes only present in the right-hand side, both sorted.
+    ///
+    /// Returns `None` if difference is too large to be decoded with this number of cells.
+    pub fn decode(mut self) -> Option<(Vec<u32>, Vec<u32>)> {
+        let (mut lhs_only, mut rhs_only) = (Vec::new(), Vec::new());
+        let mut pure: Vec<usize> = (0..self.cells()).collect();
+        while let Some(i) = pure.pop() {
+            let cell = self.cells[i];
+            if !cell.is_pure() {
+                continue;
+            }
+            if cell.count == 1 {
+                lhs_only.push(cell.key_sum);
+            } else {
+                rhs_only.push(cell.key_sum);
+            }
+            for j in self.indices(cell.key_sum) {
+                self.cells[j].count -= cell.count;
+                self.cells[j].key_sum ^= cell.key_sum;
+                self.cells[j].check_sum ^= cell.check_sum;
+                pure.push(j);
+            }
+        }
+        if self.cells.iter().any(|cell| *cell != Cell::default()) {
+            return None;
+        }
+        lhs_only.sort_unstable();
+        rhs_only.sort_unstable();
+        Some((lhs_only, rhs_only))
+    }
+
+    /// Return cell index of encoded hash in each of the partitions
+    #[inline]
+    fn indices(&self, h: u32) -> [usize; NUM_HASHES] {
+        let partition_len = self.cells() / NUM_HASHES;
+        let x = (h as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        core::array::from_fn(|k| {
+            let bits = (x >> (16 * k + 16)) & 0xffff;
+            k * partition_len + ((bits as usize * partition_len) >> 16)
+        })
+    }
+}
+
+/// Return check hash of encoded hash, which is unlikely to match XOR of several check hashes
+#[inline]
+fn check_hash(h: u32) -> u32 {
+    let x = (h as u64 ^ 0x5bd1_e995).wrapping_mul(0xff51_afd7_ed55_8ccd);
+    (x >> 32) as u32
+}
+
+impl<T, S, const P: usize, const W: usize> CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// Return `Iblt` with given number of `cells` storing encoded hashes of exact representation,
+    /// or `None` for `HyperLogLog` representation, which doesn't store individual hashes.
+    ///
+    /// Tables of two estimators using the same hasher and number of cells can be subtracted
+    /// and decoded into symmetric difference of their encoded hashes, which succeeds with high
+    /// probability while difference size stays below roughly `cells / 1.5`.
+    pub fn to_iblt(&self, cells: usize) -> Option<Iblt> {
+        let mut iblt = Iblt::new(cells);
+        match self.representation() {
+            Representation::Small(small) => {
+                for h in small.items() {
+                    if h != 0 {
+                        iblt.insert(h);
+                    }
+                }
+            }
+            Representation::Array(arr) => {
+                for &h in arr.iter() {
+                    iblt.insert(h);
+                }
+            }
+            Representation::Hll(_) => return None,
+        }
+        Some(iblt)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    /// Return sorted encoded hashes of given items
+    fn encoded_hashes(items: &[usize]) -> Vec<u32> {
+        let mut hashes: Vec<u32> = CardinalityEstimator::<usize>::from_iter(items)
+            .into_iter()
+            .collect();
+        hashes.sort_unstable();
+        hashes
+    }
+
+    #[test_case(&[], &[]; "no difference")]
+    #[test_case(&[1000, 1001], &[2000]; "few differences")]
+    #[test_case(&[1000], &[]; "left-hand side only")]
+    #[test_case(&[], &[2000, 2001, 2002]; "right-hand side only")]
+    fn test_iblt_reconcile(lhs_only: &[usize], rhs_only: &[usize]) {
+        let lhs: CardinalityEstimator<usize> = (0..100).chain(lhs_only.iter().copied()).collect();
+        let rhs: CardinalityEstimator<usize> = (0..100).chain(rhs_only.iter().copied()).collect();
+
+        let diff = lhs
+            .to_iblt(30)
+            .unwrap()
+            .subtract(&rhs.to_iblt(30).unwrap())
+            .unwrap();
+        assert_eq!(
+            diff.decode(),
+            Some((encoded_hashes(lhs_only), encoded_hashes(rhs_only)))
+        );
+    }
+
+    #[test]
+    fn test_iblt_failures() {
+        let small: CardinalityEstimator<usize> = (0..2).collect();
+        assert_eq!(small.to_iblt(10).unwrap().cells(), 12);
+        assert!(small
+            .to_iblt(12)
+            .unwrap()
+            .subtract(&small.to_iblt(30).unwrap())
+            .is_none());
+
+        let hll: CardinalityEstimator<usize> = (0..1000).collect();
+        assert!(hll.to_iblt(30).is_none());
+
+        // difference is too large for number of cells
+        let lhs: CardinalityEstimator<usize> = (0..100).collect();
+        let rhs: CardinalityEstimator<usize> = (100..200).collect();
+        let diff = lhs.to_iblt(6).unwrap().subtract(&rhs.to_iblt(6).unwrap());
+        assert!(diff.unwrap().decode().is_none());
+    }
+}
@@ -48,12 +48,42 @@
 //! # Data Storage Format
 //! The cardinality estimator stores data in one of three formats: `Small`, `Array`, and `HyperLogLog`.
 //! See corresponding modules (`small`, `array`, `hyperloglog`) for more details.
+//!
+//! # `no_std` support
+//! The crate only depends on `core` and `alloc` when built without default `std` feature,
+//! allowing to use it in embedded and bare-metal environments with a global allocator.
+//! In such builds floating point functions needed by estimates are computed in software.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
 mod array;
+mod bloom;
+pub mod bytes;
 pub mod estimator;
+pub mod frozen;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+pub mod hasher;
 mod hyperloglog;
+pub mod iblt;
+mod math;
+pub mod monitor;
+#[cfg(feature = "proto")]
+pub mod proto;
 mod representation;
 #[cfg(feature = "with_serde")]
 mod serde;
 mod small;
+pub mod sparse;
+pub mod unpacked;
 
+pub use bytes::DecodeError;
 pub use estimator::*;
+pub use frozen::FrozenEstimator;
+pub use hasher::{FixedSeedWyHash, FixedSeedWyHashBuilder, SaltedBuildHasher, SeededWyHashBuilder};
+pub use iblt::Iblt;
+pub use monitor::MergeMonitor;
+pub use representation::{InvariantError, RepresentationKind};
+pub use sparse::SparseSketch;
+pub use unpacked::UnpackedSketch;
@@ -0,0 +1,162 @@
+//! # Floating point functions
+//!
+//! `core` doesn't provide floating point functions such as `ln` or `sqrt`, as they are
+//! implemented by platform's math library. This module forwards them to `std` when `std`
+//! feature is enabled and computes them in software otherwise, so that `no_std` builds
+//! produce the same estimates (within floating point rounding).
+
+/// Largest power of two below which `f64` values may have a fractional part
+const FRACT_LIMIT: f64 = (1u64 << 52) as f64;
+
+/// Returns natural logarithm of `x`
+#[cfg(feature = "std")]
+#[inline]
+pub(crate) fn ln(x: f64) -> f64 {
+    x.ln()
+}
+
+/// Returns natural logarithm of `x`
+#[cfg(not(feature = "std"))]
+#[inline]
+pub(crate) fn ln(x: f64) -> f64 {
+    soft::ln(x)
+}
+
+/// Returns square root of `x`
+#[cfg(feature = "std")]
+#[inline]
+pub(crate) fn sqrt(x: f64) -> f64 {
+    x.sqrt()
+}
+
+/// Returns square root of `x`
+#[cfg(not(feature = "std"))]
+#[inline]
+pub(crate) fn sqrt(x: f64) -> f64 {
+    soft::sqrt(x)
+}
+
+/// Returns the largest integer less than or equal to `x`
+#[inline]
+pub(crate) fn floor(x: f64) -> f64 {
+    if x.is_nan() || x.abs() >= FRACT_LIMIT {
+        return x;
+    }
+    let t = x as i64 as f64;
+    if t > x {
+        t - 1.0
+    } else {
+        t
+    }
+}
+
+/// Returns the smallest integer greater than or equal to `x`
+#[inline]
+pub(crate) fn ceil(x: f64) -> f64 {
+    -floor(-x)
+}
+
+/// Software implementations of floating point functions used by `no_std` builds
+#[cfg_attr(feature = "std", allow(dead_code))]
+mod soft {
+    use core::f64::consts::{LN_2, SQRT_2};
+
+    /// Returns natural logarithm of `x`.
+    ///
+    /// `x` is split into `m * 2^e` with `m` in `[sqrt(2)/2..sqrt(2))` range and
+    /// `ln(m)` is computed as `2 * atanh((m - 1) / (m + 1))` power series,
+    /// which converges quickly as its argument doesn't exceed `0.172` by absolute value.
+    pub(super) fn ln(x: f64) -> f64 {
+        if x.is_nan() || x < 0.0 {
+            return f64::NAN;
+        }
+        if x == 0.0 {
+            return f64::NEG_INFINITY;
+        }
+        if x.is_infinite() {
+            return x;
+        }
+
+        let (mut m, mut e) = (x, 0);
+        if m < f64::MIN_POSITIVE {
+            // normalize subnormal values
+            m *= (1u64 << 54) as f64;
+            e -= 54;
+        }
+        let bits = m.to_bits();
+        e += ((bits >> 52) & 0x7ff) as i32 - 1023;
+        m = f64::from_bits((bits & ((1 << 52) - 1)) | (1023 << 52));
+        if m > SQRT_2 {
+            m /= 2.0;
+            e += 1;
+        }
+
+        let s = (m - 1.0) / (m + 1.0);
+        let s2 = s * s;
+        let mut term = s;
+        let mut sum = 0.0;
+        for k in (1..40).step_by(2) {
+            sum += term / k as f64;
+            term *= s2;
+        }
+        2.0 * sum + e as f64 * LN_2
+    }
+
+    /// Returns square root of `x` using Newton's method
+    pub(super) fn sqrt(x: f64) -> f64 {
+        if x.is_nan() || x < 0.0 {
+            return f64::NAN;
+        }
+        if x == 0.0 || x.is_infinite() {
+            return x;
+        }
+        // halving exponent gives initial approximation within factor of two
+        let mut y = f64::from_bits((x.to_bits() >> 1) + (1023 << 51));
+        for _ in 0..8 {
+            y = 0.5 * (y + x / y);
+        }
+        y
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(1.0)]
+    #[test_case(1.5)]
+    #[test_case(2.0)]
+    #[test_case(std::f64::consts::E)]
+    #[test_case(4097.0)]
+    #[test_case(1e-310)]
+    #[test_case(1e300)]
+    fn test_soft_ln(x: f64) {
+        let (actual, expected) = (soft::ln(x), x.ln());
+        assert!((actual - expected).abs() <= 1e-15 * expected.abs().max(1.0));
+    }
+
+    #[test_case(1.0)]
+    #[test_case(2.0)]
+    #[test_case(16.0)]
+    #[test_case(4096.0)]
+    #[test_case(262144.0)]
+    #[test_case(1e-300)]
+    fn test_soft_sqrt(x: f64) {
+        let (actual, expected) = (soft::sqrt(x), x.sqrt());
+        assert!((actual - expected).abs() <= 1e-15 * expected);
+    }
+
+    #[test_case(0.0)]
+    #[test_case(0.5)]
+    #[test_case(-0.5; "negative half")]
+    #[test_case(3.0)]
+    #[test_case(-3.0; "negative integer")]
+    #[test_case(123.999)]
+    #[test_case(-123.999; "negative fraction")]
+    #[test_case(1e20)]
+    fn test_floor_ceil(x: f64) {
+        assert_eq!(floor(x), x.floor());
+        assert_eq!(ceil(x), x.ceil());
+    }
+}
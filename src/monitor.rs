@@ -0,0 +1,94 @@
+//! ## Merge monitor
+//! Thin instrumentation layer over `CardinalityEstimator::merge` recording how estimate evolves
+//! while merging a stream of estimators, e.g. to monitor reductions of many sketches.
+use core::hash::{BuildHasher, BuildHasherDefault, Hash};
+
+use alloc::vec::Vec;
+
+use wyhash::WyHash;
+
+use crate::estimator::CardinalityEstimator;
+
+/// Cardinality estimator recording its estimate after each merge
+pub struct MergeMonitor<T, S = BuildHasherDefault<WyHash>, const P: usize = 12, const W: usize = 6>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    estimator: CardinalityEstimator<T, S, P, W>,
+    history: Vec<usize>,
+}
+
+impl<T, S, const P: usize, const W: usize> MergeMonitor<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// Create `MergeMonitor` merging into given estimator
+    #[inline]
+    pub fn new(estimator: CardinalityEstimator<T, S, P, W>) -> Self {
+        Self {
+            estimator,
+            history: Vec::new(),
+        }
+    }
+
+    /// Merge `rhs` into monitored estimator and record resulting estimate
+    #[inline]
+    pub fn merge(&mut self, rhs: &CardinalityEstimator<T, S, P, W>) {
+        self.estimator.merge(rhs);
+        self.history.push(self.estimator.estimate());
+    }
+
+    /// Return estimates recorded after each merge, in merge order
+    #[inline]
+    pub fn estimate_history(&self) -> &[usize] {
+        &self.history
+    }
+
+    /// Return reference to monitored estimator
+    #[inline]
+    pub fn estimator(&self) -> &CardinalityEstimator<T, S, P, W> {
+        &self.estimator
+    }
+
+    /// Convert `MergeMonitor` into monitored estimator
+    #[inline]
+    pub fn into_inner(self) -> CardinalityEstimator<T, S, P, W> {
+        self.estimator
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> Default for MergeMonitor<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher + Default,
+{
+    fn default() -> Self {
+        Self::new(CardinalityEstimator::new())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_history() {
+        let mut monitor = MergeMonitor::<usize>::default();
+        assert!(monitor.estimate_history().is_empty());
+
+        let mut expected = CardinalityEstimator::<usize>::new();
+        for (start, end) in [(0, 1), (0, 2), (1, 100), (50, 1000), (900, 10_000)] {
+            let rhs: CardinalityEstimator<usize> = (start..end).collect();
+            monitor.merge(&rhs);
+            expected.merge(&rhs);
+        }
+
+        let history = monitor.estimate_history();
+        assert_eq!(history, [1, 2, 100, 987, 10068]);
+        assert!(history.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(monitor.estimator().estimate(), 10068);
+        assert_eq!(monitor.into_inner(), expected);
+    }
+}
@@ -0,0 +1,296 @@
+//! # Protobuf module for CardinalityEstimator
+//!
+//! This module provides protobuf message type `SketchProto` and conversions from/to
+//! `CardinalityEstimator`, allowing to pass estimators through gRPC/protobuf based RPC layers.
+//!
+//! Message is encoded and decoded using protobuf wire format without any extra dependencies
+//! and is compatible with the following schema:
+//!
+//! ```proto
+//! syntax = "proto3";
+//!
+//! message Sketch {
+//!   // Format version, currently `1`.
+//!   uint32 version = 1;
+//!   // Precision parameter `P` in [4..18] range.
+//!   uint32 p = 2;
+//!   // Width parameter `W` in [4..6] range.
+//!   uint32 w = 3;
+//!   // Representation tag: `0` - small, `1` - array, `3` - hyperloglog.
+//!   uint32 representation = 4;
+//!   // Representation payload:
+//!   // - small: 8 bytes of little-endian `data` word;
+//!   // - array: little-endian `u32` encoded hashes;
+//!   // - hyperloglog: little-endian `u32` hyperloglog slice.
+//!   bytes payload = 5;
+//! }
+//! ```
+//!
+//! Refer to the protobuf documentation for more details on wire format:
+//! - [Encoding](https://protobuf.dev/programming-guides/encoding/)
+use core::hash::{BuildHasher, Hash};
+
+use alloc::vec::Vec;
+
+pub use crate::bytes::DecodeError;
+use crate::estimator::CardinalityEstimator;
+
+/// Current version of `SketchProto` format
+pub const PROTO_VERSION: u32 = 1;
+
+/// Protobuf wire types used by `SketchProto`
+const WIRE_VARINT: u64 = 0;
+const WIRE_FIXED64: u64 = 1;
+const WIRE_LEN: u64 = 2;
+const WIRE_FIXED32: u64 = 5;
+
+/// Protobuf message representing serialized `CardinalityEstimator`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SketchProto {
+    /// Format version
+    pub version: u32,
+    /// Precision parameter `P`
+    pub p: u32,
+    /// Width parameter `W`
+    pub w: u32,
+    /// Representation tag
+    pub representation: u32,
+    /// Representation payload
+    pub payload: Vec<u8>,
+}
+
+impl SketchProto {
+    /// Encode message into protobuf wire format.
+    /// Fields with default values are omitted as per proto3 specification.
+    pub fn encode_to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.payload.len() + 24);
+        for (field, value) in [
+            (1, self.version),
+            (2, self.p),
+            (3, self.w),
+            (4, self.representation),
+        ] {
+            if value != 0 {
+                put_varint(&mut buf, (field << 3) | WIRE_VARINT);
+                put_varint(&mut buf, value as u64);
+            }
+        }
+        if !self.payload.is_empty() {
+            put_varint(&mut buf, (5 << 3) | WIRE_LEN);
+            put_varint(&mut buf, self.payload.len() as u64);
+            buf.extend_from_slice(&self.payload);
+        }
+        buf
+    }
+
+    /// Decode message from protobuf wire format. Unknown fields are skipped.
+    pub fn decode(mut buf: &[u8]) -> Result<Self, DecodeError> {
+        let mut msg = Self::default();
+        while !buf.is_empty() {
+            let key = get_varint(&mut buf)?;
+            match (key >> 3, key & 7) {
+                (field @ 1..=4, WIRE_VARINT) => {
+                    let value = get_varint(&mut buf)? as u32;
+                    match field {
+                        1 => msg.version = value,
+                        2 => msg.p = value,
+                        3 => msg.w = value,
+                        _ => msg.representation = value,
+                    }
+                }
+                (5, WIRE_LEN) => msg.payload = get_bytes(&mut buf)?.to_vec(),
+                (_, WIRE_VARINT) => {
+                    get_varint(&mut buf)?;
+                }
+                (_, WIRE_FIXED64) => {
+                    get_fixed(&mut buf, 8)?;
+                }
+                (_, WIRE_LEN) => {
+                    get_bytes(&mut buf)?;
+                }
+                (_, WIRE_FIXED32) => {
+                    get_fixed(&mut buf, 4)?;
+                }
+                _ => return Err(DecodeError::InvalidMessage),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// Convert `CardinalityEstimator` into protobuf message
+    pub fn to_proto(&self) -> SketchProto {
+        let (representation, payload) = self.to_payload();
+        SketchProto {
+            version: PROTO_VERSION,
+            p: P as u32,
+            w: W as u32,
+            representation: representation as u32,
+            payload,
+        }
+    }
+
+    /// Create `CardinalityEstimator` from protobuf message
+    pub fn from_proto(proto: &SketchProto) -> Result<Self, DecodeError>
+    where
+        S: Default,
+    {
+        if proto.version != PROTO_VERSION {
+            return Err(DecodeError::UnsupportedVersion {
+                found: proto.version,
+                max_supported: PROTO_VERSION,
+            });
+        }
+        if proto.p as usize != P || proto.w as usize != W {
+            return Err(DecodeError::ParamsMismatch);
+        }
+        let tag =
+            u8::try_from(proto.representation).map_err(|_| DecodeError::InvalidRepresentation)?;
+        Self::from_payload(tag, &proto.payload)
+    }
+}
+
+/// Append base 128 varint to buffer
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Read base 128 varint from buffer
+fn get_varint(buf: &mut &[u8]) -> Result<u64, DecodeError> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf.split_first().ok_or(DecodeError::InvalidMessage)?;
+        *buf = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte < 0x80 {
+            return Ok(value);
+        }
+    }
+    Err(DecodeError::InvalidMessage)
+}
+
+/// Read `len` bytes from buffer
+fn get_fixed<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], DecodeError> {
+    if buf.len() < len {
+        return Err(DecodeError::InvalidMessage);
+    }
+    let (bytes, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(bytes)
+}
+
+/// Read length-delimited bytes from buffer
+fn get_bytes<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8], DecodeError> {
+    let len = get_varint(buf)?;
+    get_fixed(
+        buf,
+        usize::try_from(len).map_err(|_| DecodeError::InvalidMessage)?,
+    )
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0; "empty set")]
+    #[test_case(1; "single element")]
+    #[test_case(2; "two distinct elements")]
+    #[test_case(3; "three distinct elements")]
+    #[test_case(100; "hundred distinct elements")]
+    #[test_case(10000; "ten thousand distinct elements")]
+    fn test_proto_round_trip(n: usize) {
+        let mut original = CardinalityEstimator::<usize>::new();
+        for i in 0..n {
+            original.insert(&i);
+        }
+
+        let bytes = original.to_proto().encode_to_vec();
+        let proto = SketchProto::decode(&bytes).expect("decoding failed");
+        assert_eq!(proto, original.to_proto());
+
+        let mut decoded = CardinalityEstimator::<usize>::from_proto(&proto).unwrap();
+        assert_eq!(decoded.to_proto(), proto);
+        assert_eq!(decoded.estimate(), original.estimate());
+
+        // decoded estimator must remain fully functional
+        for i in n..n + 200 {
+            original.insert(&i);
+            decoded.insert(&i);
+        }
+        assert_eq!(decoded.estimate(), original.estimate());
+    }
+
+    #[test]
+    fn test_proto_skips_unknown_fields() {
+        let mut e = CardinalityEstimator::<usize>::new();
+        e.insert(&1);
+        // field 6 varint 150 and field 7 with two bytes length-delimited value
+        let mut bytes = vec![0x30, 0x96, 0x01, 0x3a, 0x02, 0xab, 0xcd];
+        bytes.extend(e.to_proto().encode_to_vec());
+        let proto = SketchProto::decode(&bytes).unwrap();
+        assert_eq!(
+            CardinalityEstimator::<usize>::from_proto(&proto).unwrap(),
+            e
+        );
+    }
+
+    #[test]
+    fn test_proto_invalid() {
+        let e = CardinalityEstimator::<usize>::new();
+        let proto = e.to_proto();
+
+        assert_eq!(
+            SketchProto::decode(&[0x08]),
+            Err(DecodeError::InvalidMessage)
+        );
+        assert_eq!(
+            SketchProto::decode(&[0x2a, 0x05, 0x00]),
+            Err(DecodeError::InvalidMessage)
+        );
+
+        let invalid = SketchProto {
+            version: 2,
+            ..proto.clone()
+        };
+        let result = CardinalityEstimator::<usize>::from_proto(&invalid);
+        assert_eq!(
+            result.unwrap_err(),
+            DecodeError::UnsupportedVersion {
+                found: 2,
+                max_supported: PROTO_VERSION
+            }
+        );
+
+        let invalid = SketchProto {
+            p: 10,
+            ..proto.clone()
+        };
+        let result = CardinalityEstimator::<usize>::from_proto(&invalid);
+        assert_eq!(result.unwrap_err(), DecodeError::ParamsMismatch);
+
+        let invalid = SketchProto {
+            representation: 2,
+            ..proto.clone()
+        };
+        let result = CardinalityEstimator::<usize>::from_proto(&invalid);
+        assert_eq!(result.unwrap_err(), DecodeError::InvalidRepresentation);
+
+        let invalid = SketchProto {
+            representation: 3,
+            payload: vec![0; 12],
+            ..proto
+        };
+        let result = CardinalityEstimator::<usize>::from_proto(&invalid);
+        assert_eq!(result.unwrap_err(), DecodeError::InvalidRepresentation);
+    }
+}
@@ -1,4 +1,8 @@
-use std::hash::{Hash, Hasher};
+use core::hash::{BuildHasher, Hash};
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
 
 use enum_dispatch::enum_dispatch;
 
@@ -24,6 +28,42 @@ pub(crate) enum Representation<'a, const P: usize, const W: usize> {
     Hll(HyperLogLog<'a, P, W>),
 }
 
+/// Kind of `CardinalityEstimator` representation, whose discriminant is the representation
+/// tag stored in the lowest 2 bits of `data` and in encoded estimators
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RepresentationKind {
+    /// Inline representation of up to 2 encoded hashes
+    Small = REPRESENTATION_SMALL as u8,
+    /// Heap allocated array of encoded hashes
+    Array = REPRESENTATION_ARRAY as u8,
+    /// Heap allocated `HyperLogLog` registers
+    Hll = REPRESENTATION_HLL as u8,
+}
+
+impl RepresentationKind {
+    /// Return representation kind of given tag, or `None` for unknown tag
+    #[inline]
+    pub const fn from_u8(tag: u8) -> Option<Self> {
+        match tag as usize {
+            REPRESENTATION_SMALL => Some(Self::Small),
+            REPRESENTATION_ARRAY => Some(Self::Array),
+            REPRESENTATION_HLL => Some(Self::Hll),
+            _ => None,
+        }
+    }
+
+    /// Return label of representation kind: `"small"`, `"array"` or `"hll"`
+    #[inline]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Small => "small",
+            Self::Array => "array",
+            Self::Hll => "hll",
+        }
+    }
+}
+
 /// Representation trait which must be implemented by all representations.
 #[enum_dispatch(Representation<P, W>)]
 pub(crate) trait RepresentationTrait {
@@ -32,11 +72,33 @@ pub(crate) trait RepresentationTrait {
     fn size_of(&self) -> usize;
     unsafe fn drop(&mut self);
     fn to_data(&self) -> usize;
+    fn verify_invariants(&self) -> Result<(), InvariantError>;
     fn to_string(&self) -> String {
         format!("estimate: {}, size: {}", self.estimate(), self.size_of())
     }
 }
 
+/// Representation invariant violation returned by `CardinalityEstimator::verify_invariants`
+#[derive(Debug, PartialEq)]
+pub enum InvariantError {
+    /// `Small` representation stores 2-nd hash while 1-st hash is empty
+    SmallInvalidOrder,
+    /// `Small` representation stores the same hash twice
+    SmallDuplicateHash(u32),
+    /// `Array` representation length is out of `[3..MAX_CAPACITY]` range
+    ArrayInvalidLength(usize),
+    /// `Array` representation stores zero hash at given index
+    ArrayZeroHash(usize),
+    /// `Array` representation stores the same hash more than once
+    ArrayDuplicateHash(u32),
+    /// `Array` representation has non-zero element in unused capacity at given index
+    ArrayNonZeroPadding(usize),
+    /// `HyperLogLog` representation stored number of zero registers doesn't match registers
+    HllZerosMismatch { stored: u32, actual: u32 },
+    /// `HyperLogLog` representation stored harmonic sum doesn't match registers
+    HllSumMismatch { stored: f32, actual: f64 },
+}
+
 /// Representation error
 #[derive(Debug)]
 pub enum RepresentationError {
@@ -68,16 +130,26 @@ impl<'a, const P: usize, const W: usize> Representation<'a, P, W> {
         }
     }
 
+    /// Return kind of representation
+    #[inline]
+    pub(crate) fn kind(&self) -> RepresentationKind {
+        match self {
+            Representation::Small(_) => RepresentationKind::Small,
+            Representation::Array(_) => RepresentationKind::Array,
+            Representation::Hll(_) => RepresentationKind::Hll,
+        }
+    }
+
     /// Create new cardinality estimator from data and optional vector
-    pub fn try_from<T, H>(
+    pub fn try_from<T, S>(
         data: usize,
         opt_vec: Option<Vec<u32>>,
-    ) -> Result<CardinalityEstimator<T, H, P, W>, RepresentationError>
+    ) -> Result<CardinalityEstimator<T, S, P, W>, RepresentationError>
     where
         T: Hash + ?Sized,
-        H: Hasher + Default,
+        S: BuildHasher + Default,
     {
-        let mut estimator = CardinalityEstimator::<T, H, P, W>::new();
+        let mut estimator = CardinalityEstimator::<T, S, P, W>::new();
         estimator.data = match data & REPRESENTATION_MASK {
             REPRESENTATION_SMALL if opt_vec.is_some() => return Err(SmallRepresentationInvalid),
             REPRESENTATION_SMALL => Small::<P, W>::from(data).to_data(),
@@ -87,7 +159,13 @@ impl<'a, const P: usize, const W: usize> Representation<'a, P, W> {
                 if len <= 2 || len > MAX_CAPACITY {
                     return Err(ArrayRepresentationInvalid);
                 }
-                Array::<P, W>::from_vec(vec, len).to_data()
+                // array capacity is always the next power of two of its length, allocated
+                // exactly as it's later freed as a slice of that length
+                let cap = len.next_power_of_two();
+                let mut arr = Vec::with_capacity(cap);
+                arr.extend_from_slice(&vec);
+                arr.resize(cap, 0);
+                Array::<P, W>::from_vec(arr, len).to_data()
             }
             REPRESENTATION_HLL => {
                 let vec = opt_vec.ok_or(HllRepresentationInvalid)?;
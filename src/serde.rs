@@ -16,8 +16,11 @@
 //! Refer to the serde documentation for more details on custom serialization and deserialization:
 //! - [Serialization](https://serde.rs/impl-serialize.html)
 //! - [Deserialization](https://serde.rs/impl-deserialize.html)
-use std::hash::{Hash, Hasher};
-use std::ops::Deref;
+use core::hash::{BuildHasher, Hash};
+use core::ops::Deref;
+
+use alloc::format;
+use alloc::vec::Vec;
 
 use serde::de::Error;
 use serde::ser::SerializeTuple;
@@ -26,10 +29,10 @@ use serde::{Deserialize, Serialize};
 use crate::estimator::CardinalityEstimator;
 use crate::representation::Representation;
 
-impl<T, H, const P: usize, const W: usize> Serialize for CardinalityEstimator<T, H, P, W>
+impl<T, B, const P: usize, const W: usize> Serialize for CardinalityEstimator<T, B, P, W>
 where
     T: Hash + ?Sized,
-    H: Hasher + Default,
+    B: BuildHasher,
 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -61,11 +64,11 @@ where
     }
 }
 
-impl<'de, T, H, const P: usize, const W: usize> Deserialize<'de>
-    for CardinalityEstimator<T, H, P, W>
+impl<'de, T, B, const P: usize, const W: usize> Deserialize<'de>
+    for CardinalityEstimator<T, B, P, W>
 where
     T: Hash + ?Sized,
-    H: Hasher + Default,
+    B: BuildHasher + Default,
 {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -87,6 +90,7 @@ pub mod tests {
     #[test_case(0; "empty set")]
     #[test_case(1; "single element")]
     #[test_case(2; "two distinct elements")]
+    #[test_case(3; "three distinct elements")]
     #[test_case(100; "hundred distinct elements")]
     #[test_case(10000; "ten thousand distinct elements")]
     fn test_serde(n: usize) {
@@ -94,7 +98,7 @@ pub mod tests {
 
         for i in 0..n {
             let item = &format!("item{}", i);
-            original_estimator.insert(&item);
+            original_estimator.insert(item);
         }
 
         let serialized = serde_json::to_string(&original_estimator).expect("serialization failed");
@@ -103,13 +107,24 @@ pub mod tests {
             "serialized string should not be empty"
         );
 
-        let deserialized_estimator: CardinalityEstimator<str> =
+        let mut deserialized_estimator: CardinalityEstimator<str> =
             serde_json::from_str(&serialized).expect("deserialization failed");
 
         assert_eq!(
             original_estimator.representation(),
             deserialized_estimator.representation()
         );
+
+        // deserialized estimator must remain fully functional
+        for i in n..n + 200 {
+            let item = &format!("item{}", i);
+            original_estimator.insert(item);
+            deserialized_estimator.insert(item);
+        }
+        assert_eq!(
+            original_estimator.estimate(),
+            deserialized_estimator.estimate()
+        );
     }
 
     #[test]
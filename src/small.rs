@@ -6,13 +6,17 @@
 //! - 2..33 bits    - store 31-bit encoded hash
 //! - 34..63 bits   - store 31-bit encoded hash
 
-use std::fmt::{Debug, Formatter};
+use core::fmt::{Debug, Formatter};
+
+use alloc::vec;
 
 use crate::array::Array;
-use crate::representation::RepresentationTrait;
+use crate::representation::{InvariantError, RepresentationTrait};
 
 /// Mask used for extracting hashes stored in small representation (31 bits)
 const SMALL_MASK: usize = 0x0000_0000_7fff_ffff;
+/// Maximum number of encoded hashes stored in small representation
+pub(crate) const SMALL_CAPACITY: usize = 2;
 
 /// Small representation container
 #[derive(PartialEq)]
@@ -56,7 +60,7 @@ impl<const P: usize, const W: usize> Small<P, W> {
 
     /// Return items stored within `Small` representation
     #[inline]
-    pub(crate) fn items(&self) -> [u32; 2] {
+    pub(crate) fn items(&self) -> [u32; SMALL_CAPACITY] {
         [self.h1(), self.h2()]
     }
 }
@@ -86,7 +90,7 @@ impl<const P: usize, const W: usize> RepresentationTrait for Small<P, W> {
 
     /// Return memory size of `Small` representation
     fn size_of(&self) -> usize {
-        std::mem::size_of::<Self>()
+        core::mem::size_of::<Self>()
     }
 
     /// Free memory occupied by the `Small` representation
@@ -98,10 +102,20 @@ impl<const P: usize, const W: usize> RepresentationTrait for Small<P, W> {
     fn to_data(&self) -> usize {
         self.0
     }
+
+    /// Verify that hashes are stored in order and are distinct
+    fn verify_invariants(&self) -> Result<(), InvariantError> {
+        match (self.h1(), self.h2()) {
+            (0, 0) | (_, 0) => Ok(()),
+            (0, _) => Err(InvariantError::SmallInvalidOrder),
+            (h1, h2) if h1 == h2 => Err(InvariantError::SmallDuplicateHash(h1)),
+            (_, _) => Ok(()),
+        }
+    }
 }
 
 impl<const P: usize, const W: usize> Debug for Small<P, W> {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         f.write_str(&self.to_string())
     }
 }
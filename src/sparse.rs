@@ -0,0 +1,244 @@
+//! ## Sparse sketch
+//! Compact owned form of HyperLogLog sketch storing only non-zero registers, which uses far less
+//! memory than dense `HyperLogLog` representation for low cardinalities, e.g. to keep thousands
+//! of sketches resident just for occasional merges.
+//!
+//! Non-zero registers are stored as `u32` words sorted by register index:
+//! - 0..W bits        - store register rank
+//! - W..W+P bits      - store register index
+//!
+//! This is the same layout as sparse encoding of hashes used by `CardinalityEstimator`, so sparse
+//! registers are decoded into `(idx, rank)` pairs the same way as encoded hashes.
+use core::fmt::{Debug, Formatter};
+use core::hash::{BuildHasher, Hash};
+use core::mem::size_of;
+use core::ops::Deref;
+
+use alloc::vec::Vec;
+
+use crate::estimator::CardinalityEstimator;
+use crate::hyperloglog::HyperLogLog;
+use crate::representation::{Representation, RepresentationTrait};
+
+/// Sparse sketch storing sorted non-zero HyperLogLog registers
+#[derive(Clone, Default, PartialEq)]
+pub struct SparseSketch<const P: usize = 12, const W: usize = 6> {
+    /// Sorted non-zero registers encoded as `(idx << W) | rank`
+    registers: Vec<u32>,
+}
+
+impl<const P: usize, const W: usize> SparseSketch<P, W> {
+    /// Number of bytes used by `2^P` registers of `W` bits in dense `HyperLogLog` representation
+    pub const DENSE_REGISTER_BYTES: usize = HyperLogLog::<P, W>::M * W / 8;
+
+    /// Number of non-zero registers (close to cardinality while it's much smaller than `2^P`)
+    /// starting from which sparse sketch, storing `u32` word per non-zero register, is no
+    /// longer smaller than dense registers.
+    pub const SPARSE_BREAKEVEN: usize = Self::DENSE_REGISTER_BYTES.div_ceil(size_of::<u32>());
+
+    /// Create sparse sketch from given representation
+    fn from_representation(representation: &Representation<P, W>) -> Self {
+        let mut registers = match representation {
+            Representation::Small(small) => small
+                .items()
+                .into_iter()
+                .filter(|&h| h != 0)
+                .map(Self::register_from_hash)
+                .collect(),
+            Representation::Array(arr) => arr
+                .deref()
+                .iter()
+                .map(|&h| Self::register_from_hash(h))
+                .collect(),
+            Representation::Hll(hll) => (0..HyperLogLog::<P, W>::M as u32)
+                .map(|idx| (idx << W) | hll.get_register(idx))
+                .filter(|&r| r & ((1 << W) - 1) != 0)
+                .collect(),
+        };
+        Self::normalize(&mut registers);
+        registers.shrink_to_fit();
+        Self { registers }
+    }
+
+    /// Convert encoded hash into sparse register
+    #[inline]
+    fn register_from_hash(h: u32) -> u32 {
+        let (idx, rank) = HyperLogLog::<P, W>::decode_hash(h);
+        (idx << W) | rank
+    }
+
+    /// Sort registers and keep only the maximum rank of each register index
+    fn normalize(registers: &mut Vec<u32>) {
+        // sorting by `(idx, rank)` places maximum rank last among equal indices
+        registers.sort_unstable();
+        registers.reverse();
+        registers.dedup_by_key(|r| *r >> W);
+        registers.reverse();
+    }
+
+    /// Return iterator over non-zero registers as `(idx, rank)` pairs sorted by index
+    pub fn registers(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.registers
+            .iter()
+            .map(|&r| HyperLogLog::<P, W>::decode_hash(r))
+    }
+
+    /// Return cardinality estimate of `SparseSketch`
+    pub fn estimate(&self) -> usize {
+        let zeros = HyperLogLog::<P, W>::M - self.registers.len();
+        let sum = zeros as f64
+            + self
+                .registers()
+                .map(|(_, rank)| 1.0 / (1u64 << rank) as f64)
+                .sum::<f64>();
+        (HyperLogLog::<P, W>::estimate_from(zeros as u32, sum) + 0.5) as usize
+    }
+
+    /// Merge cardinality estimator into `SparseSketch`
+    pub fn merge<T, S>(&mut self, rhs: &CardinalityEstimator<T, S, P, W>)
+    where
+        T: Hash + ?Sized,
+        S: BuildHasher,
+    {
+        self.merge_sparse(&Self::from_representation(&rhs.representation()));
+    }
+
+    /// Merge two sparse sketches
+    pub fn merge_sparse(&mut self, rhs: &Self) {
+        self.registers.extend_from_slice(&rhs.registers);
+        Self::normalize(&mut self.registers);
+    }
+
+    /// Convert `SparseSketch` back into dense `CardinalityEstimator`
+    pub fn into_dense<T, S>(self) -> CardinalityEstimator<T, S, P, W>
+    where
+        T: Hash + ?Sized,
+        S: BuildHasher + Default,
+    {
+        let mut estimator = CardinalityEstimator::new();
+        estimator.merge_sparse(&self);
+        estimator
+    }
+
+    /// Return memory size of `SparseSketch`
+    pub fn size_of(&self) -> usize {
+        size_of::<Self>() + self.registers.capacity() * size_of::<u32>()
+    }
+}
+
+impl<const P: usize, const W: usize> Debug for SparseSketch<P, W> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "Sparse(estimate: {}, size: {})",
+            self.estimate(),
+            self.size_of()
+        )
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// Convert `CardinalityEstimator` into compact `SparseSketch` freeing its registers.
+    ///
+    /// Exact representations are converted into HyperLogLog registers,
+    /// so their estimates become approximate.
+    pub fn into_sparse(self) -> SparseSketch<P, W> {
+        SparseSketch::from_representation(&self.representation())
+    }
+
+    /// Merge `SparseSketch` into `CardinalityEstimator`, converting it into
+    /// `HyperLogLog` representation.
+    pub fn merge_sparse(&mut self, rhs: &SparseSketch<P, W>) {
+        self.upgrade_to_hll();
+        let Representation::Hll(mut hll) = self.representation() else {
+            unreachable!("estimator must be in HyperLogLog representation");
+        };
+        for &r in &rhs.registers {
+            hll.insert_encoded_hash(r);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0 => "Sparse(estimate: 0, size: 24)")]
+    #[test_case(1 => "Sparse(estimate: 1, size: 28)")]
+    #[test_case(100 => "Sparse(estimate: 100, size: 420)")]
+    #[test_case(200 => "Sparse(estimate: 198, size: 800)")]
+    #[test_case(1000 => "Sparse(estimate: 987, size: 3532)")]
+    fn test_into_sparse(n: usize) -> String {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..n {
+            e.insert(&i);
+        }
+        let estimate = e.estimate();
+        let sparse = e.into_sparse();
+        assert!(sparse.estimate().abs_diff(estimate) <= 1);
+
+        let dense: CardinalityEstimator<usize> = sparse.clone().into_dense();
+        assert_eq!(dense.estimate(), sparse.estimate());
+        assert_eq!(dense.clone().into_sparse(), sparse);
+
+        format!("{:?}", sparse)
+    }
+
+    #[test]
+    fn test_sparse_merge() {
+        let mut lhs = CardinalityEstimator::<usize>::new();
+        let mut rhs = CardinalityEstimator::<usize>::new();
+        for i in 0..1000 {
+            lhs.insert(&i);
+            rhs.insert(&(i + 500));
+        }
+
+        let mut merged = lhs.clone();
+        merged.merge(&rhs);
+
+        let mut sparse = lhs.clone().into_sparse();
+        sparse.merge(&rhs);
+        assert_eq!(sparse, merged.clone().into_sparse());
+        assert!(sparse.estimate().abs_diff(merged.estimate()) <= 1);
+
+        lhs.merge_sparse(&rhs.into_sparse());
+        assert_eq!(lhs.into_sparse(), merged.into_sparse());
+    }
+
+    fn check_sparse_breakeven<const P: usize, const W: usize>() {
+        let dense_bytes = SparseSketch::<P, W>::DENSE_REGISTER_BYTES;
+        assert_eq!(dense_bytes * 8, (1 << P) * W);
+        let breakeven = (0..=1 << P)
+            .find(|&registers| registers * size_of::<u32>() >= dense_bytes)
+            .unwrap();
+        assert_eq!(SparseSketch::<P, W>::SPARSE_BREAKEVEN, breakeven);
+    }
+
+    #[test]
+    fn test_sparse_breakeven() {
+        check_sparse_breakeven::<4, 4>();
+        check_sparse_breakeven::<4, 5>();
+        check_sparse_breakeven::<4, 6>();
+        check_sparse_breakeven::<12, 5>();
+        check_sparse_breakeven::<12, 6>();
+        check_sparse_breakeven::<18, 6>();
+        assert_eq!(SparseSketch::<12, 6>::SPARSE_BREAKEVEN, 768);
+
+        // sparse registers of actual sketches are smaller than dense ones below breakeven
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..2000 {
+            e.insert(&i);
+            let sparse = e.clone().into_sparse();
+            let sparse_bytes = sparse.size_of() - size_of::<SparseSketch>();
+            assert_eq!(
+                sparse_bytes < SparseSketch::<12, 6>::DENSE_REGISTER_BYTES,
+                sparse.registers().count() < SparseSketch::<12, 6>::SPARSE_BREAKEVEN
+            );
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! ## Unpacked sketch
+//! Alternative dense layout of HyperLogLog registers storing each register in its own byte
+//! instead of packing registers into `W` bits. It uses `8 / W` times more memory than packed
+//! `HyperLogLog` representation (e.g. 4096 vs 3072 bytes of registers for `P = 12`, `W = 6`),
+//! but merging two sketches is a plain byte-wise maximum, which compilers vectorize into SIMD
+//! instructions, making it well suited for reducing large numbers of sketches.
+//!
+//! Unpacked sketches are converted back into packed `CardinalityEstimator` for serialization.
+use core::fmt::{Debug, Formatter};
+use core::hash::{BuildHasher, Hash};
+use core::mem::size_of;
+use core::ops::Deref;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::estimator::CardinalityEstimator;
+use crate::hyperloglog::HyperLogLog;
+use crate::representation::{Representation, RepresentationTrait};
+
+/// Sketch storing HyperLogLog registers unpacked into one byte per register
+#[derive(Clone, PartialEq)]
+pub struct UnpackedSketch<const P: usize = 12, const W: usize = 6> {
+    /// Register ranks indexed by register index
+    registers: Vec<u8>,
+}
+
+impl<const P: usize, const W: usize> UnpackedSketch<P, W> {
+    /// Create new empty `UnpackedSketch`
+    pub fn new() -> Self {
+        Self {
+            registers: vec![0; HyperLogLog::<P, W>::M],
+        }
+    }
+
+    /// Create unpacked sketch from given representation
+    fn from_representation(representation: &Representation<P, W>) -> Self {
+        let mut sketch = Self::new();
+        match representation {
+            Representation::Small(small) => {
+                for h in small.items() {
+                    if h != 0 {
+                        sketch.insert_encoded_hash(h);
+                    }
+                }
+            }
+            Representation::Array(arr) => {
+                for &h in arr.deref() {
+                    sketch.insert_encoded_hash(h);
+                }
+            }
+            Representation::Hll(hll) => {
+                for (idx, rank) in sketch.registers.iter_mut().enumerate() {
+                    *rank = hll.get_register(idx as u32) as u8;
+                }
+            }
+        }
+        sketch
+    }
+
+    /// Update register of encoded hash
+    #[inline]
+    fn insert_encoded_hash(&mut self, h: u32) {
+        let (idx, rank) = HyperLogLog::<P, W>::decode_hash(h);
+        let register = &mut self.registers[idx as usize];
+        *register = (*register).max(rank as u8);
+    }
+
+    /// Return iterator over registers as `(idx, rank)` pairs sorted by index
+    pub fn registers(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.registers
+            .iter()
+            .enumerate()
+            .map(|(idx, &rank)| (idx as u32, rank as u32))
+    }
+
+    /// Return cardinality estimate of `UnpackedSketch`
+    pub fn estimate(&self) -> usize {
+        let (zeros, sum) = self.accounting();
+        (HyperLogLog::<P, W>::estimate_from(zeros, sum) + 0.5) as usize
+    }
+
+    /// Return number of zero registers and registers' harmonic sum.
+    ///
+    /// Registers are scanned in fixed size chunks accumulated into independent lanes, which
+    /// compilers vectorize into SIMD instructions. `2^-rank` is computed without table lookups
+    /// or divisions by building `f64` with exponent `-rank` directly from register's bits.
+    /// Lanes fix summation order, so the sum is bit-identical whether the loop is vectorized.
+    pub fn accounting(&self) -> (u32, f64) {
+        /// Number of lanes accumulated independently
+        const LANES: usize = 16;
+        /// Exponent bias of `f64`, i.e. bits of exponent `0`
+        const EXP_BIAS: u64 = 1023;
+
+        let mut zeros = [0u32; LANES];
+        let mut sums = [0.0f64; LANES];
+        let chunks = self.registers.chunks_exact(LANES);
+        let remainder = chunks.remainder();
+        for chunk in chunks {
+            for lane in 0..LANES {
+                let rank = chunk[lane];
+                zeros[lane] += (rank == 0) as u32;
+                sums[lane] += f64::from_bits((EXP_BIAS - rank as u64) << 52);
+            }
+        }
+        for &rank in remainder {
+            zeros[0] += (rank == 0) as u32;
+            sums[0] += f64::from_bits((EXP_BIAS - rank as u64) << 52);
+        }
+        (zeros.iter().sum(), sums.iter().sum())
+    }
+
+    /// Merge cardinality estimator into `UnpackedSketch`
+    pub fn merge<T, S>(&mut self, rhs: &CardinalityEstimator<T, S, P, W>)
+    where
+        T: Hash + ?Sized,
+        S: BuildHasher,
+    {
+        self.merge_unpacked(&Self::from_representation(&rhs.representation()));
+    }
+
+    /// Merge two unpacked sketches by taking maximum rank of each register
+    #[inline]
+    pub fn merge_unpacked(&mut self, rhs: &Self) {
+        for (lhs, &rhs) in self.registers.iter_mut().zip(&rhs.registers) {
+            *lhs = (*lhs).max(rhs);
+        }
+    }
+
+    /// Convert `UnpackedSketch` back into packed `CardinalityEstimator`
+    pub fn into_dense<T, S>(self) -> CardinalityEstimator<T, S, P, W>
+    where
+        T: Hash + ?Sized,
+        S: BuildHasher + Default,
+    {
+        let mut estimator = CardinalityEstimator::new();
+        estimator.merge_unpacked(&self);
+        estimator
+    }
+
+    /// Return memory size of `UnpackedSketch`
+    pub fn size_of(&self) -> usize {
+        size_of::<Self>() + self.registers.capacity()
+    }
+}
+
+impl<const P: usize, const W: usize> Default for UnpackedSketch<P, W> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const P: usize, const W: usize> Debug for UnpackedSketch<P, W> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "Unpacked(estimate: {}, size: {})",
+            self.estimate(),
+            self.size_of()
+        )
+    }
+}
+
+impl<T, S, const P: usize, const W: usize> CardinalityEstimator<T, S, P, W>
+where
+    T: Hash + ?Sized,
+    S: BuildHasher,
+{
+    /// Return `UnpackedSketch` holding registers of `CardinalityEstimator`.
+    ///
+    /// Exact representations are converted into HyperLogLog registers,
+    /// so their estimates become approximate.
+    pub fn to_unpacked(&self) -> UnpackedSketch<P, W> {
+        UnpackedSketch::from_representation(&self.representation())
+    }
+
+    /// Merge `UnpackedSketch` into `CardinalityEstimator`, converting it into
+    /// `HyperLogLog` representation.
+    pub fn merge_unpacked(&mut self, rhs: &UnpackedSketch<P, W>) {
+        self.upgrade_to_hll();
+        let Representation::Hll(mut hll) = self.representation() else {
+            unreachable!("estimator must be in HyperLogLog representation");
+        };
+        for (idx, rank) in rhs.registers() {
+            if rank != 0 {
+                hll.insert_encoded_hash((idx << W) | rank);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(0 => "Unpacked(estimate: 0, size: 4120)")]
+    #[test_case(1 => "Unpacked(estimate: 1, size: 4120)")]
+    #[test_case(100 => "Unpacked(estimate: 100, size: 4120)")]
+    #[test_case(10_000 => "Unpacked(estimate: 10068, size: 4120)")]
+    fn test_to_unpacked(n: usize) -> String {
+        let e: CardinalityEstimator<usize> = (0..n).collect();
+        let unpacked = e.to_unpacked();
+        assert!(unpacked.estimate().abs_diff(e.estimate()) <= 1);
+
+        let dense: CardinalityEstimator<usize> = unpacked.clone().into_dense();
+        assert_eq!(dense.to_unpacked(), unpacked);
+
+        format!("{:?}", unpacked)
+    }
+
+    #[test_case(10, 20; "exact representations")]
+    #[test_case(1000, 20; "hyperloglog and exact representations")]
+    #[test_case(10_000, 50_000; "hyperloglog representations")]
+    fn test_unpacked_merge(lhs_n: usize, rhs_n: usize) {
+        let lhs: CardinalityEstimator<usize> = (0..lhs_n).collect();
+        let rhs: CardinalityEstimator<usize> = (lhs_n / 2..lhs_n / 2 + rhs_n).collect();
+
+        let mut packed = lhs.clone();
+        packed.merge(&rhs);
+        packed.upgrade_to_hll();
+
+        let mut unpacked = lhs.to_unpacked();
+        unpacked.merge_unpacked(&rhs.to_unpacked());
+        assert!(unpacked.estimate().abs_diff(packed.estimate()) <= 1);
+
+        let mut merged = UnpackedSketch::new();
+        merged.merge(&lhs);
+        merged.merge(&rhs);
+        assert_eq!(merged, unpacked);
+
+        let dense: CardinalityEstimator<usize> = unpacked.into_dense();
+        assert_eq!(dense.to_bytes(), packed.to_bytes());
+    }
+
+    #[test_case(0; "empty")]
+    #[test_case(100; "sparse")]
+    #[test_case(10_000; "dense")]
+    #[test_case(1_000_000; "saturated")]
+    fn test_accounting(n: usize) {
+        let e: CardinalityEstimator<usize> = (0..n).collect();
+        let unpacked = e.to_unpacked();
+
+        // scalar recomputation
+        let mut zeros = 0;
+        let mut sum = 0.0f64;
+        for (_, rank) in unpacked.registers() {
+            zeros += (rank == 0) as u32;
+            sum += 1.0 / (1u64 << rank) as f64;
+        }
+        let (simd_zeros, simd_sum) = unpacked.accounting();
+        assert_eq!(simd_zeros, zeros);
+        assert!((simd_sum - sum).abs() <= 1e-12 * sum);
+
+        let scalar_estimate = (HyperLogLog::<12, 6>::estimate_from(zeros, sum) + 0.5) as usize;
+        assert_eq!(unpacked.estimate(), scalar_estimate);
+        // packed representation's incrementally maintained `f32` sum may slightly drift
+        assert!(unpacked.estimate().abs_diff(e.estimate()) <= 1 + n / 10_000);
+    }
+
+    #[test]
+    fn test_accounting_max_rank() {
+        let mut unpacked = UnpackedSketch::<4, 4>::new();
+        unpacked.registers.fill(15);
+        assert_eq!(unpacked.accounting(), (0, 16.0 / (1 << 15) as f64));
+        let mut unpacked = UnpackedSketch::<4, 6>::new();
+        unpacked.registers.fill(63);
+        assert_eq!(unpacked.accounting(), (0, 16.0 / (1u64 << 63) as f64));
+    }
+}
@@ -0,0 +1,97 @@
+//! Verifies number of heap allocations made by estimator operations using `dhat` heap profiler.
+//! Profiler counts allocations of all threads, so checks run sequentially within single test.
+#[global_allocator]
+static ALLOC: dhat::Alloc = dhat::Alloc;
+
+use cardinality_estimator::CardinalityEstimator;
+
+/// Return number of heap blocks allocated while running `f`
+fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, u64) {
+    let _profiler = dhat::Profiler::builder().testing().build();
+    let result = f();
+    (result, dhat::HeapStats::get().total_blocks)
+}
+
+/// Merging `Small` into `Array` allocates resulting array once, without intermediate growth
+fn check_merge_small_with_array() {
+    for (lhs_items, rhs_n, expected_size) in [
+        (&[1000, 2000], 3, 8 + 8 * 4),
+        (&[1000, 2000], 7, 8 + 16 * 4),
+        (&[1000, 2000], 127, 8 + (4096 * 6 / 32 + 3) * 4),
+    ] {
+        let mut lhs: CardinalityEstimator<usize> = lhs_items.iter().collect();
+        let rhs: CardinalityEstimator<usize> = (0..rhs_n).collect();
+        let ((), blocks) = count_allocations(|| lhs.merge(&rhs));
+        assert_eq!(blocks, 1, "merge of {lhs_items:?} into {rhs_n} items");
+        assert_eq!(lhs.size_of(), expected_size);
+    }
+}
+
+/// Merging `Array` into `Array` appends missing hashes in place if they fit into capacity,
+/// otherwise allocates resulting array once
+fn check_merge_arrays() {
+    for (lhs_n, rhs_range, expected_blocks, expected_size) in [
+        (5, 3..8, 0, 8 + 8 * 4),
+        (5, 0..5, 0, 8 + 8 * 4),
+        (5, 3..20, 1, 8 + 32 * 4),
+        (40, 0..100, 1, 8 + 128 * 4),
+    ] {
+        let mut lhs: CardinalityEstimator<usize> = (0..lhs_n).collect();
+        let rhs: CardinalityEstimator<usize> = rhs_range.clone().collect();
+        let ((), blocks) = count_allocations(|| lhs.merge(&rhs));
+        assert_eq!(
+            blocks, expected_blocks,
+            "merge of {rhs_range:?} into {lhs_n} items"
+        );
+        assert_eq!(lhs.size_of(), expected_size);
+        assert_eq!(lhs.estimate(), lhs_n.max(rhs_range.end));
+    }
+}
+
+/// Reserving distinct items allocates `HyperLogLog` once instead of growing `Array` first,
+/// while collecting duplicated items doesn't allocate beyond their distinct count
+fn check_reserve() {
+    let (e, blocks) = count_allocations(|| {
+        let mut e = CardinalityEstimator::<usize>::new();
+        e.reserve(1000);
+        for i in 0..1000 {
+            e.insert(&i);
+        }
+        e
+    });
+    assert_eq!((e.estimate(), blocks), (987, 1));
+
+    let (e, blocks) = count_allocations(|| {
+        let mut e = CardinalityEstimator::<usize>::new();
+        for i in 0..1000 {
+            e.insert(&i);
+        }
+        e
+    });
+    // `Array` grows through capacities 4, 8, ..., 128 before `HyperLogLog` is allocated
+    assert_eq!((e.estimate(), blocks), (987, 7));
+
+    let items = vec![1; 1000];
+    let (e, blocks) = count_allocations(|| items.iter().collect::<CardinalityEstimator<usize>>());
+    assert_eq!((e.estimate(), e.size_of(), blocks), (1, 8, 0));
+}
+
+/// Merging exact estimator into `HyperLogLog` allocates only copy of `rhs` registers,
+/// which exact hashes of `self` are inserted into
+fn check_merge_exact_into_hll() {
+    let rhs: CardinalityEstimator<usize> = (0..10_000).collect();
+    for lhs_n in [2, 100] {
+        let mut lhs: CardinalityEstimator<usize> = (10_000..10_000 + lhs_n).collect();
+        let ((), blocks) = count_allocations(|| lhs.merge(&rhs));
+        assert_eq!(blocks, 1, "merge of {lhs_n} items into hyperloglog");
+        assert_eq!(lhs.size_of(), rhs.size_of());
+    }
+}
+
+#[test]
+fn test_allocations() {
+    check_merge_small_with_array();
+    check_merge_arrays();
+    check_reserve();
+    check_merge_exact_into_hll();
+}
@@ -0,0 +1,106 @@
+//! Verifies that estimator allocations are freed with the same layout size they were allocated
+//! with, e.g. after deserialization, using global allocator tracking live allocation sizes.
+#![cfg(feature = "with_serde")]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use cardinality_estimator::CardinalityEstimator;
+
+/// Number of tracked allocation slots
+const SLOTS: usize = 1 << 16;
+/// Slot marker of freed allocation, which never matches a valid pointer
+const TOMBSTONE: usize = 1;
+
+static PTRS: [AtomicUsize; SLOTS] = [const { AtomicUsize::new(0) }; SLOTS];
+static SIZES: [AtomicUsize; SLOTS] = [const { AtomicUsize::new(0) }; SLOTS];
+static MISMATCHES: AtomicUsize = AtomicUsize::new(0);
+
+/// Allocator counting deallocations whose layout size differs from allocated size
+struct LayoutCheckingAlloc;
+
+#[global_allocator]
+static ALLOC: LayoutCheckingAlloc = LayoutCheckingAlloc;
+
+fn slot(ptr: usize) -> usize {
+    (ptr >> 4).wrapping_mul(0x9e37_79b9_7f4a_7c15) % SLOTS
+}
+
+fn record(ptr: usize, size: usize) {
+    let mut idx = slot(ptr);
+    for _ in 0..SLOTS {
+        for empty in [0, TOMBSTONE] {
+            if PTRS[idx]
+                .compare_exchange(empty, usize::MAX, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+            {
+                SIZES[idx].store(size, Ordering::Release);
+                PTRS[idx].store(ptr, Ordering::Release);
+                return;
+            }
+        }
+        idx = (idx + 1) % SLOTS;
+    }
+}
+
+fn take(ptr: usize) -> Option<usize> {
+    let mut idx = slot(ptr);
+    for _ in 0..SLOTS {
+        match PTRS[idx].load(Ordering::Acquire) {
+            0 => return None,
+            p if p == ptr => {
+                let size = SIZES[idx].load(Ordering::Acquire);
+                PTRS[idx].store(TOMBSTONE, Ordering::Release);
+                return Some(size);
+            }
+            _ => idx = (idx + 1) % SLOTS,
+        }
+    }
+    None
+}
+
+unsafe impl GlobalAlloc for LayoutCheckingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            record(ptr as usize, layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if take(ptr as usize).is_some_and(|size| size != layout.size()) {
+            MISMATCHES.fetch_add(1, Ordering::Relaxed);
+        }
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Round trip estimator of `n` items through JSON, which grows deserialized vectors
+/// beyond their length
+fn json_round_trip(n: usize) -> CardinalityEstimator<usize> {
+    let e: CardinalityEstimator<usize> = (0..n).collect();
+    let json = serde_json::to_string(&e).unwrap();
+    let decoded: CardinalityEstimator<usize> = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, e);
+    decoded
+}
+
+#[test]
+fn test_deserialized_layouts() {
+    for n in [3, 5, 100, 10_000] {
+        drop(json_round_trip(n));
+    }
+    assert_eq!(MISMATCHES.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn test_merge_take_buffer_deserialized() {
+    for (n, capacity) in [(3, 4), (100, 128), (10_000, 4096 * 6 / 32 + 3)] {
+        let mut lhs = CardinalityEstimator::<usize>::new();
+        let buffer = lhs.merge_take_buffer(json_round_trip(n)).unwrap();
+        assert_eq!(buffer.capacity(), capacity);
+        drop(buffer);
+    }
+    assert_eq!(MISMATCHES.load(Ordering::Relaxed), 0);
+}
@@ -0,0 +1,37 @@
+//! Exercises core estimator operations from `no_std` crate.
+//! Run with `cargo test --no-default-features` to test the crate built without `std`.
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use cardinality_estimator::CardinalityEstimator;
+
+#[test]
+fn test_no_std_insert_estimate_merge() {
+    let mut lhs = CardinalityEstimator::<u64>::new();
+    let mut rhs = CardinalityEstimator::<u64>::new();
+    let mut estimates = Vec::new();
+    for i in 0..10_000 {
+        lhs.insert(&i);
+        rhs.insert(&(i + 5_000));
+        if (i + 1) % 2_500 == 0 {
+            estimates.push(lhs.estimate());
+        }
+    }
+    assert_eq!(estimates, [2512, 5012, 7546, 10068]);
+
+    lhs.merge(&rhs);
+    assert_eq!(lhs.estimate(), 14925);
+}
+
+#[test]
+fn test_no_std_exact_representations() {
+    let mut e = CardinalityEstimator::<str>::new();
+    for i in 0..100 {
+        e.insert(["a", "b", "c", "d", "e"][i % 5]);
+        assert_eq!(e.estimate(), (i + 1).min(5));
+    }
+    assert!(e.verify_invariants().is_ok());
+}